@@ -0,0 +1,45 @@
+use concurrent_arena::{Arena, ArenaArc};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rayon::prelude::*;
+
+const BITARRAY_LEN: usize = 4;
+const LEN: usize = BITARRAY_LEN * usize::BITS as usize;
+
+type BenchArena = Arena<usize, BITARRAY_LEN, LEN>;
+
+/// Insert into a bucket where only the last bit of every `usize` is free,
+/// so allocation has to skip over the bits already allocated.
+fn insert_mostly_full(c: &mut Criterion) {
+    let arena = BenchArena::with_limit(1, 1);
+
+    let bits = usize::BITS as usize;
+    let arcs: Vec<_> = (0..LEN).map(|i| arena.insert(i)).collect();
+    let (last, _kept): (Vec<_>, Vec<_>) = arcs
+        .into_iter()
+        .partition(|arc| ArenaArc::slot(arc) as usize % bits == bits - 1);
+    for arc in last {
+        ArenaArc::remove(&arc);
+    }
+
+    c.bench_function("insert_mostly_full", |b| {
+        b.iter(|| ArenaArc::remove(&black_box(arena.insert(0))))
+    });
+}
+
+/// Insert and remove from all threads at once, so that they contend
+/// on the same `usize`s.
+fn insert_contended(c: &mut Criterion) {
+    let arena = BenchArena::with_limit(1, 1);
+
+    c.bench_function("insert_contended", |b| {
+        b.iter(|| {
+            (0..LEN * 4).into_par_iter().for_each(|i| {
+                ArenaArc::remove(&black_box(arena.insert(i)));
+            })
+        })
+    });
+}
+
+criterion_group!(benches, insert_mostly_full, insert_contended);
+criterion_main!(benches);
@@ -1,180 +1,283 @@
-#![forbid(unsafe_code)]
-
-use core::{marker::PhantomData, ops::Deref, slice::Iter};
-
-use arc_swap::{ArcSwapAny, Guard};
-use parking_lot::Mutex;
-
-use triomphe::ThinArc;
+use core::{
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    ops::Deref,
+    ptr::{self, null_mut},
+};
+use std::sync::atomic::{
+    AtomicPtr, AtomicUsize,
+    Ordering::{Acquire, Relaxed, Release},
+};
+
+use super::Arc;
+
+/// Number of segments, segment `k` holds `2^k` elements.
+pub(crate) const SEGMENTS: usize = u32::BITS as usize;
+
+/// Maximum number of elements `Arcs` can hold.
+const MAX_LEN: usize = u32::MAX as usize;
+
+/// Return the segment and the offset in it of `index`.
+pub(crate) fn locate(index: usize) -> (usize, usize) {
+    let pos = index + 1;
+    let segment = (usize::BITS - 1 - pos.leading_zeros()) as usize;
+
+    (segment, pos - (1 << segment))
+}
 
+/// Lock-free growable array of `Arc<T>`.
+///
+/// The elements are stored in segments of size power of 2, which are
+/// allocated on demand and never moved, thus growing never copies the
+/// existing elements and reading never needs any guard.
 #[derive(Debug)]
 pub(crate) struct Arcs<T> {
-    array: ArcSwapAny<Option<ThinArc<(), T>>>,
-    mutex: Mutex<()>,
+    segments: [AtomicPtr<AtomicPtr<T>>; SEGMENTS],
+    /// Every element below `len` is initialized.
+    len: AtomicUsize,
+    _marker: PhantomData<Arc<T>>,
 }
 
 impl<T> Arcs<T> {
     pub(crate) fn new() -> Self {
         Self {
-            array: ArcSwapAny::new(None),
-            mutex: Mutex::new(()),
+            segments: Default::default(),
+            len: AtomicUsize::new(0),
+            _marker: PhantomData,
         }
     }
 
     pub(crate) fn as_slice(&self) -> Slice<'_, T> {
-        Slice(self.array.load(), PhantomData)
+        Slice {
+            arcs: self,
+            len: self.len(),
+        }
     }
 
     pub(crate) fn len(&self) -> usize {
-        self.as_slice().len()
+        self.len.load(Acquire)
     }
 
     pub(crate) fn is_empty(&self) -> bool {
         self.len() == 0
     }
-}
 
-impl<T: Clone> Arcs<T> {
-    pub(crate) fn grow(&self, new_len: usize, f: impl FnMut() -> T) {
-        if self.len() < new_len {
-            let _guard = self.mutex.lock();
-            self.do_grow(new_len, f);
+    /// Return the slot at `index`, allocating its segment if necessary.
+    fn slot_or_alloc(&self, index: usize) -> &AtomicPtr<T> {
+        let (segment, offset) = locate(index);
+        let atomic = &self.segments[segment];
+
+        let mut ptr = atomic.load(Acquire);
+        if ptr.is_null() {
+            let new = Box::into_raw(
+                (0..(1_usize << segment))
+                    .map(|_| AtomicPtr::<T>::new(null_mut()))
+                    .collect::<Box<[_]>>(),
+            )
+            .cast::<AtomicPtr<T>>();
+
+            ptr = match atomic.compare_exchange(ptr, new, Release, Acquire) {
+                Ok(_) => new,
+                Err(curr) => {
+                    // Another thread has allocated the segment.
+                    //
+                    // Safety: `new` is just allocated above with this length.
+                    drop(unsafe { Self::segment_from_raw(new, segment) });
+                    curr
+                }
+            };
         }
-    }
 
-    /// This function is technically lock-free despite the fact that `self.mutex` is
-    /// used, since it only `try_lock` the mutex.
-    pub(crate) fn try_grow(&self, new_len: usize, f: impl FnMut() -> T) -> Result<(), ()> {
-        if self.len() < new_len {
-            if let Some(_guard) = self.mutex.try_lock() {
-                self.do_grow(new_len, f);
-                Ok(())
-            } else {
-                Err(())
-            }
-        } else {
-            Ok(())
-        }
+        // Safety: segment `segment` has `2^segment` slots.
+        unsafe { &*ptr.add(offset) }
     }
 
-    fn do_grow(&self, new_len: usize, f: impl FnMut() -> T) {
-        let slice = self.as_slice();
+    /// # Safety
+    ///
+    /// `ptr` must be the segment `segment` allocated in `slot_or_alloc`.
+    unsafe fn segment_from_raw(ptr: *mut AtomicPtr<T>, segment: usize) -> Box<[AtomicPtr<T>]> {
+        Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, 1 << segment))
+    }
 
-        let old_len = slice.len();
-        if old_len >= new_len {
+    /// Grow the array to `new_len`, creating the new elements with `f`.
+    ///
+    /// This function is lock-free, if multiple threads grow the array
+    /// concurrently then each slot takes the element of whichever thread
+    /// sets it first.
+    pub(crate) fn grow(&self, new_len: usize, mut f: impl FnMut() -> Arc<T>) {
+        let len = self.len();
+        if len >= new_len {
             return;
         }
 
-        struct Initializer<'a, T, F>(Iter<'a, T>, usize, F);
+        assert!(new_len <= MAX_LEN, "Arcs cannot hold {} elements", new_len);
 
-        impl<T: Clone, F: FnMut() -> T> Iterator for Initializer<'_, T, F> {
-            type Item = T;
+        for index in len..new_len {
+            let slot = self.slot_or_alloc(index);
 
-            fn next(&mut self) -> Option<T> {
-                if let Some(val) = self.0.next() {
-                    Some(val.clone())
-                } else if self.1 != 0 {
-                    self.1 -= 1;
-                    Some(self.2())
-                } else {
-                    None
+            if slot.load(Acquire).is_null() {
+                let new = Arc::into_raw(f()) as *mut T;
+
+                if slot
+                    .compare_exchange(null_mut(), new, Release, Acquire)
+                    .is_err()
+                {
+                    // Safety: `new` is created by `Arc::into_raw` above.
+                    drop(unsafe { Arc::from_raw(new) });
                 }
             }
+        }
+
+        // Every slot below `new_len` is observed to be initialized with
+        // `Acquire`, thus publishing `len` with `Release` also publishes them.
+        self.len.fetch_max(new_len, Release);
+    }
 
-            fn size_hint(&self) -> (usize, Option<usize>) {
-                let len = self.0.len() + self.1;
+    /// Drop all elements at and after `new_len`, along with the segments
+    /// that become empty.
+    pub(crate) fn truncate(&mut self, new_len: usize) {
+        let len = self.len.get_mut();
+        *len = (*len).min(new_len);
 
-                (len, Some(len))
+        for (segment, atomic) in self.segments.iter_mut().enumerate() {
+            let ptr = *atomic.get_mut();
+            if ptr.is_null() {
+                continue;
             }
-        }
 
-        impl<T: Clone, F: FnMut() -> T> ExactSizeIterator for Initializer<'_, T, F> {}
+            let start = (1 << segment) - 1;
+            let end = start + (1 << segment);
+            if end <= new_len {
+                continue;
+            }
 
-        let arc =
-            ThinArc::from_header_and_iter((), Initializer(slice.iter(), new_len - old_len, f));
+            // Safety: `ptr` is a segment allocated by `slot_or_alloc` and
+            // there is no other reference to it, given `&mut self`.
+            let mut slots = unsafe { Self::segment_from_raw(ptr, segment) };
+
+            // Slots after `len` might also be initialized if `f` in `grow`
+            // panics, so check every one of them.
+            for (index, slot) in (start..end).zip(slots.iter_mut()) {
+                let ptr = *slot.get_mut();
+                if index >= new_len && !ptr.is_null() {
+                    *slot.get_mut() = null_mut();
+                    // Safety: `ptr` is created by `Arc::into_raw` in `grow`.
+                    drop(unsafe { Arc::from_raw(ptr) });
+                }
+            }
 
-        let _old = self.array.swap(Some(arc));
+            if start < new_len {
+                // The segment is still in use.
+                *atomic.get_mut() = Box::into_raw(slots).cast();
+            } else {
+                *atomic.get_mut() = null_mut();
+            }
+        }
+    }
+}
 
-        #[cfg(debug_assertions)]
-        debug_assert!(slice.is_same_arc(_old.as_ref()));
+impl<T> Drop for Arcs<T> {
+    fn drop(&mut self) {
+        self.truncate(0);
     }
 }
 
-/// Slice is just a temporary borrow of the object.
-pub(crate) struct Slice<'a, T>(Guard<Option<ThinArc<(), T>>>, PhantomData<&'a Arcs<T>>);
+/// A snapshot of the first `len` elements of `Arcs`.
+///
+/// Elements are only dropped through `&mut Arcs`, thus they stay valid
+/// for as long as the borrow.
+pub(crate) struct Slice<'a, T> {
+    arcs: &'a Arcs<T>,
+    len: usize,
+}
 
-impl<T> Slice<'_, T> {
-    #[cfg(debug_assertions)]
-    fn is_same_arc(&self, other: Option<&ThinArc<(), T>>) -> bool {
-        let this = self.0.as_ref();
-        if this.is_none() && other.is_none() {
-            return true;
+impl<T> Clone for Slice<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Slice<'_, T> {}
+
+impl<'a, T> Slice<'a, T> {
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Option<ArcRef<'a, T>> {
+        if index >= self.len {
+            return None;
         }
 
-        let this = if let Some(this) = this {
-            this
-        } else {
-            return false;
-        };
+        let (segment, offset) = locate(index);
 
-        let other = if let Some(other) = other {
-            other
-        } else {
-            return false;
+        // Safety: the segment and the slot are published before `len`, which
+        // is loaded with `Acquire`, and they are never freed while `Arcs`
+        // is borrowed.
+        let ptr = unsafe {
+            let segment = self.arcs.segments[segment].load(Acquire);
+            (*segment.add(offset)).load(Relaxed)
         };
+        debug_assert!(!ptr.is_null());
+
+        // Safety: `ptr` is created by `Arc::into_raw` in `Arcs::grow`.
+        let arc = unsafe { Arc::from_raw(ptr) };
+
+        Some(ArcRef(ManuallyDrop::new(arc), PhantomData))
+    }
 
-        this.heap_ptr() == other.heap_ptr()
+    pub(crate) fn iter(&self) -> impl Iterator<Item = ArcRef<'a, T>> + 'a {
+        let this = *self;
+        (0..this.len).filter_map(move |index| this.get(index))
     }
 }
 
-impl<T> Deref for Slice<'_, T> {
-    type Target = [T];
+/// Borrow of an element in `Arcs` without touching its reference count.
+pub(crate) struct ArcRef<'a, T>(ManuallyDrop<Arc<T>>, PhantomData<&'a Arcs<T>>);
+
+impl<T> Deref for ArcRef<'_, T> {
+    type Target = Arc<T>;
 
     fn deref(&self) -> &Self::Target {
-        self.0
-            .as_ref()
-            .map(ThinArc::deref)
-            .map(|header_slice| &header_slice.slice)
-            .unwrap_or(&[])
+        &self.0
     }
 }
 
-/// Thread sanitizer produces false positive in this test.
-///
-/// This has been discussed in
-/// [this issue](https://github.com/vorner/arc-swap/issues/71)
-/// and the failure can only be reproduced on x86-64-unknown-linux-gnu.
-/// It cannot be reproduced on MacOS.
-///
-/// Since crate arc-swap is a cross platform crate with no assembly used
-/// or any x86 specific feature, this can be some bugs in the allocator
-/// or the thread sanitizer.
-#[cfg(not(feature = "thread-sanitizer"))]
 #[cfg(test)]
 mod tests {
-    use super::Arcs;
+    use super::{locate, Arcs};
 
+    use crate::Arc;
     use parking_lot::Mutex;
-    use std::sync::Arc;
 
     use rayon::prelude::*;
 
+    #[test]
+    fn test_locate() {
+        assert_eq!(locate(0), (0, 0));
+        assert_eq!(locate(1), (1, 0));
+        assert_eq!(locate(2), (1, 1));
+        assert_eq!(locate(3), (2, 0));
+        assert_eq!(locate(6), (2, 3));
+        assert_eq!(locate(7), (3, 0));
+    }
+
     #[test]
     fn test() {
-        let bag: Arc<Arcs<Arc<Mutex<u32>>>> = Arc::new(Arcs::new());
+        let bag: Arc<Arcs<Mutex<u32>>> = Arc::new(Arcs::new());
         assert_eq!(bag.len(), 0);
         assert!(bag.is_empty());
 
         {
             let slice = bag.as_slice();
-            assert!(slice.is_empty());
             assert_eq!(slice.len(), 0);
+            assert!(slice.get(0).is_none());
         }
 
         bag.grow(10, Arc::default);
         {
             let slice = bag.as_slice();
-            assert!(!slice.is_empty());
+            assert_eq!(slice.len(), 10);
 
             for (i, arc) in slice.iter().enumerate() {
                 *arc.lock() = i as u32;
@@ -188,11 +291,28 @@ mod tests {
 
         {
             let slice = bag.as_slice();
-            assert!(!slice.is_empty());
+            assert!(slice.len() >= 10 + 32);
+            assert_eq!(slice.iter().count(), slice.len());
 
             for (i, arc) in slice.iter().take(10).enumerate() {
-                *arc.lock() = i as u32;
+                assert_eq!(*arc.lock(), i as u32);
             }
         }
+
+        let mut bag = Arc::try_unwrap(bag).unwrap();
+        let arc = Arc::clone(&bag.as_slice().get(2).unwrap());
+
+        bag.truncate(3);
+        assert_eq!(bag.len(), 3);
+        assert_eq!(*bag.as_slice().get(2).unwrap().lock(), 2);
+        assert!(bag.segments[2].get_mut().is_null());
+
+        bag.truncate(0);
+        assert!(bag.is_empty());
+        assert!(bag.segments.iter_mut().all(|ptr| ptr.get_mut().is_null()));
+        assert!(arc.is_unique());
+
+        bag.grow(4, Arc::default);
+        assert_eq!(*bag.as_slice().get(3).unwrap().lock(), 0);
     }
 }
@@ -1,4 +1,18 @@
-use super::{arcs::Arcs, bucket::Bucket, thread_id::get_thread_id, Arc, ArenaArc};
+use super::{
+    arcs::{Arcs, Slice},
+    bucket::{self, Bucket},
+    cursor::{ArenaId, Cursor},
+    shared::Shared,
+    thread_id::get_thread_id,
+    Arc, ArenaArc, ArenaBox, BucketStats, Error, InsertError, Key, VacantSlot,
+};
+
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "async")]
+use super::shared::AsyncWaiter;
+#[cfg(feature = "async")]
+use core::future::poll_fn;
 
 /// * `LEN` - Number of elements stored per bucket.
 ///   Must be less than or equal to `u32::MAX`, divisible by
@@ -12,16 +26,17 @@ use super::{arcs::Arcs, bucket::Bucket, thread_id::get_thread_id, Arc, ArenaArc}
 /// `Arena` stores the elements in buckets to ensure that the address
 /// for elements are stable while improving efficiency.
 ///
-/// Every bucket is of size `LEN`.
+/// Every bucket is of size `LEN`, though the memory for its elements is only
+/// allocated once the first slot in it is reserved.
 ///
 /// The larger `LEN` is, the more compact the `Arena` will be, however it might
 /// also waste space if it is unused.
 ///
 /// And, allocating a large chunk of memory takes more time.
 ///
-/// `Arena` internally stores the array of buckets as a `triomphe::ThinArc`
-/// and use `ArcSwapAny` to grow the array atomically, without blocking any
-/// reader.
+/// `Arena` internally stores the buckets in segments, where segment `k` holds
+/// `2^k` buckets, so that growing is lock-free and never copies or blocks
+/// any reader.
 ///
 /// # Examples
 ///
@@ -42,7 +57,12 @@ use super::{arcs::Arcs, bucket::Bucket, thread_id::get_thread_id, Arc, ArenaArc}
 /// ```
 #[derive(Debug)]
 pub struct Arena<T, const BITARRAY_LEN: usize, const LEN: usize> {
-    buckets: Arcs<Arc<Bucket<T, BITARRAY_LEN, LEN>>>,
+    pub(crate) buckets: Arcs<Bucket<T, BITARRAY_LEN, LEN>>,
+    pub(crate) shared: Arc<Shared>,
+    /// Maximum buckets this `Arena` can have, at most `Self::max_buckets()`.
+    limit: u32,
+    /// Used to find the cursor of the current thread.
+    id: ArenaId,
 }
 
 impl<T: Sync + Send, const BITARRAY_LEN: usize, const LEN: usize> Default
@@ -84,67 +104,203 @@ impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Arena<T, BITAR
     }
 
     pub fn with_capacity(cap: u32) -> Self {
+        Self::with_limit(cap, Self::max_buckets())
+    }
+
+    /// Create an `Arena` with `cap` buckets preallocated, which can grow
+    /// to at most `min(limit, Self::max_buckets())` buckets.
+    ///
+    /// Once the limit is reached, [`Arena::insert`] blocks until a slot is
+    /// freed.
+    ///
+    /// # Panics
+    ///
+    /// If `limit` is `0`, since no slot could ever be freed to unblock
+    /// [`Arena::insert`].
+    pub fn with_limit(cap: u32, limit: u32) -> Self {
         const { check_const_generics::<BITARRAY_LEN, LEN>() };
 
-        let cap = cap.min(Self::max_buckets());
-        let buckets = Arcs::new();
+        assert!(limit > 0, "Arena cannot have a limit of 0 buckets");
 
-        buckets.grow(cap as usize, Arc::default);
+        let this = Self {
+            buckets: Arcs::new(),
+            shared: Arc::default(),
+            limit: limit.min(Self::max_buckets()),
+            id: ArenaId::default(),
+        };
+        this.reserve(cap);
 
-        Self { buckets }
+        this
     }
 
-    /// Return Ok(arc) on success, or Err((value, len)) where value is
-    /// the input param `value` and `len` is the length of the `Arena` at the time
-    /// of insertion.
+    /// Maximum buckets this `Arena` can have.
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    fn new_bucket(&self) -> Arc<Bucket<T, BITARRAY_LEN, LEN>> {
+        Bucket::new(Arc::clone(&self.shared))
+    }
+
+    /// Return `InsertError::Full` if all buckets are full but more buckets can
+    /// still be reserved, or `InsertError::CapacityExhausted` if all buckets
+    /// are full and the `Arena` already has `self.limit()` buckets.
     ///
     /// This function is lock-free.
-    pub fn try_insert(&self, mut value: T) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, (T, u32)> {
+    pub fn try_insert(&self, value: T) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, InsertError<T>> {
+        match self.try_reserve_slot() {
+            Ok(vacant) => Ok(vacant.insert(value)),
+            Err(len) if len == self.limit => Err(InsertError::CapacityExhausted { value }),
+            Err(len) => Err(InsertError::Full { value, len }),
+        }
+    }
+
+    /// Insert `value` at exactly `slot`, reserving more buckets if necessary,
+    /// so that slots can be restored with the same numbers, e.g. when
+    /// rebuilding an `Arena` from a snapshot.
+    ///
+    /// Return the value back if the slot is occupied, reserved by
+    /// a [`VacantSlot`], or is not in any of the `self.limit()` buckets.
+    pub fn try_insert_at(&self, slot: u32, value: T) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, T> {
+        let bucket_index = slot / (LEN as u32);
+        let index = slot % (LEN as u32);
+
+        if bucket_index >= self.limit {
+            return Err(value);
+        }
+
+        self.reserve(bucket_index + 1);
+
+        let slice = self.buckets.as_slice();
+        let bucket = match slice.get(bucket_index as usize) {
+            Some(bucket) => bucket,
+            None => unreachable!("bucket {} is just reserved", bucket_index),
+        };
+
+        // Safety: index is < LEN
+        match unsafe { Bucket::try_reserve_at(&bucket, bucket_index, index) } {
+            Some(vacant) => Ok(vacant.insert(value)),
+            None => Err(value),
+        }
+    }
+
+    /// Return the cursor of the current thread if it is in the first `len`
+    /// buckets.
+    fn load_cursor(&self, len: usize) -> Option<Cursor> {
+        self.id
+            .load_cursor()
+            .filter(|cursor| (cursor.bucket as usize) < len)
+    }
+
+    /// Return the bucket to start searching for a free slot from, which is
+    /// the bucket the current thread last reserved a slot in, so that its
+    /// insertions stay in the same cache lines.
+    fn start_bucket(cursor: Option<Cursor>, len: usize) -> usize {
+        cursor.map_or_else(|| get_thread_id() % len, |cursor| cursor.bucket as usize)
+    }
+
+    fn store_cursor(&self, vacant: &VacantSlot<T, BITARRAY_LEN, LEN>) {
+        self.id.store_cursor(Cursor::new(vacant.slot(), LEN as u32));
+    }
+
+    /// Return `Err(len)` if all `len` buckets are full.
+    ///
+    /// Buckets marked as full in `Shared::full_buckets` are skipped, so that
+    /// it does not need to visit every bucket when the `Arena` is mostly full.
+    fn try_reserve_slot(&self) -> Result<VacantSlot<T, BITARRAY_LEN, LEN>, u32> {
         let slice = self.buckets.as_slice();
         let len = slice.len();
 
-        debug_assert!(len <= Self::max_buckets() as usize);
+        debug_assert!(len <= self.limit as usize);
 
         if len == 0 {
-            return Err((value, 0));
+            return Err(0);
         }
 
-        let mut pos = get_thread_id() % len;
+        let cursor = self.load_cursor(len);
 
-        let slice1_iter = slice[pos..].iter();
-        let slice2_iter = slice[..pos].iter();
+        // Only visit buckets that are not full.
+        let full_buckets = &self.shared.full_buckets;
+        for pos in full_buckets.not_full(Self::start_bucket(cursor, len), len) {
+            let bucket = match slice.get(pos) {
+                Some(bucket) => bucket,
+                None => unreachable!("bucket {} is less than len {}", pos, len),
+            };
 
-        for bucket in slice1_iter.chain(slice2_iter) {
-            match Bucket::try_insert(bucket, pos as u32, value) {
-                Ok(arc) => return Ok(arc),
-                Err(val) => value = val,
-            }
+            let word = cursor
+                .filter(|cursor| cursor.bucket as usize == pos)
+                .map(|cursor| cursor.word as usize);
 
-            pos = (pos + 1) % len;
+            if let Some(vacant) = Bucket::try_reserve(&bucket, pos as u32, word) {
+                self.store_cursor(&vacant);
+                return Ok(vacant);
+            }
         }
 
-        Err((value, len as u32))
+        Err(len as u32)
     }
 
-    /// Try to reserve `min(new_len, Self::max_buckets())` buckets.
+    /// Reserve at most `n` slots and append them to `vacants`, using one
+    /// load of the buckets for all of them.
     ///
-    /// This function is technically lock-free.
-    pub fn try_reserve(&self, new_len: u32) -> bool {
-        if new_len == 0 {
-            return true;
+    /// Return the number of buckets.
+    fn try_reserve_slots(
+        &self,
+        n: usize,
+        vacants: &mut Vec<VacantSlot<T, BITARRAY_LEN, LEN>>,
+    ) -> u32 {
+        let slice = self.buckets.as_slice();
+        let len = slice.len();
+
+        debug_assert!(len <= self.limit as usize);
+
+        if len == 0 {
+            return 0;
         }
 
-        let new_len = new_len.min(Self::max_buckets());
-        self.buckets
-            .try_grow(new_len as usize, Arc::default)
-            .is_ok()
+        let mut remaining = n;
+        let start = Self::start_bucket(self.load_cursor(len), len);
+
+        let full_buckets = &self.shared.full_buckets;
+        for pos in full_buckets.not_full(start, len) {
+            if remaining == 0 {
+                break;
+            }
+
+            let bucket = match slice.get(pos) {
+                Some(bucket) => bucket,
+                None => unreachable!("bucket {} is less than len {}", pos, len),
+            };
+
+            remaining -= Bucket::try_reserve_many(&bucket, pos as u32, remaining, vacants);
+        }
+
+        if let Some(vacant) = vacants.last() {
+            self.store_cursor(vacant);
+        }
+
+        len as u32
+    }
+
+    /// Try to reserve `min(new_len, self.limit())` buckets.
+    ///
+    /// Since reserving buckets is lock-free, it always succeeds and is
+    /// the same as [`Arena::reserve`].
+    pub fn try_reserve(&self, new_len: u32) -> bool {
+        self.reserve(new_len);
+        true
     }
 
-    /// Reserve `min(new_len, Self::max_buckets())` buckets.
+    /// Reserve `min(new_len, self.limit())` buckets.
+    ///
+    /// This function is lock-free.
     pub fn reserve(&self, new_len: u32) {
         if new_len != 0 {
-            let new_len = new_len.min(Self::max_buckets());
-            self.buckets.grow(new_len as usize, Arc::default)
+            let new_len = new_len.min(self.limit) as usize;
+
+            // Every bucket has its bit before it is published.
+            self.shared.full_buckets.grow(new_len);
+            self.buckets.grow(new_len, || self.new_bucket())
         }
     }
 
@@ -152,45 +308,178 @@ impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Arena<T, BITAR
     ///
     /// If there isn't enough buckets, then try to reserve one bucket and
     /// restart the operation.
-    pub fn insert(&self, mut value: T) -> ArenaArc<T, BITARRAY_LEN, LEN> {
-        // Fast path where `try_reserve` is used to avoid locking.
-        for _ in 0..5 {
-            match self.try_insert(value) {
-                Ok(arc) => return arc,
-                Err((val, len)) => {
-                    value = val;
-
-                    // If len == Self::max_buckets(), then we would have to
-                    // wait for slots to be removed from `Arena`.
-                    if len != Self::max_buckets() {
-                        // If try_reserve succeeds, then another new bucket is available.
-                        //
-                        // If try_reserve fail, then another thread is doing the
-                        // reservation.
-                        //
-                        // We can simply restart operation, waiting for it to be done.
-                        //
-                        // Grow by 1.5 exponential to have amoritized O(1), adding +4 more in case
-                        // there's only one element (1 * 3 / 2 evaluaes to 1 in rust).
-                        self.try_reserve(len * 3 / 2 + 4);
-                    }
+    ///
+    /// If the `Arena` already has `self.limit()` buckets, then block
+    /// until a slot is freed by dropping the last `ArenaArc` to a removed
+    /// element.
+    ///
+    /// The [`Key`] of the element, to be used with [`Arena::get_by_key`]
+    /// and [`Arena::remove_by_key`], is obtained by [`ArenaArc::key`].
+    pub fn insert(&self, value: T) -> ArenaArc<T, BITARRAY_LEN, LEN> {
+        self.reserve_slot().insert(value)
+    }
+
+    /// Insert one value that is uniquely owned by the returned [`ArenaBox`]
+    /// and can be mutated without any synchronization, until it is shared
+    /// by [`ArenaBox::into_shared`].
+    ///
+    /// It reserves new buckets or blocks in the same way as [`Arena::insert`].
+    pub fn insert_unique(&self, value: T) -> ArenaBox<T, BITARRAY_LEN, LEN> {
+        self.reserve_slot().insert_unique(value)
+    }
+
+    /// Same as [`Arena::insert`], except that the value is created by
+    /// `f`, which receives the slot the value will be inserted into.
+    ///
+    /// [`Arena::get`] on the slot returns `Error::Vacant` until `f` returns.
+    pub fn insert_with(&self, f: impl FnOnce(u32) -> T) -> ArenaArc<T, BITARRAY_LEN, LEN> {
+        let vacant = self.reserve_slot();
+        let value = f(vacant.slot());
+        vacant.insert(value)
+    }
+
+    /// Reserve a slot, so that its slot can be used to create the value
+    /// before it is inserted by [`VacantSlot::insert`].
+    ///
+    /// It reserves new buckets or blocks in the same way as [`Arena::insert`].
+    pub fn reserve_slot(&self) -> VacantSlot<T, BITARRAY_LEN, LEN> {
+        match self.reserve_slot_impl(None) {
+            Some(vacant) => vacant,
+            None => unreachable!("reserve_slot without deadline never times out"),
+        }
+    }
+
+    /// Insert all values of `values` and return their `ArenaArc`s in the
+    /// same order.
+    ///
+    /// Compared to calling [`Arena::insert`] in a loop, slots are reserved
+    /// in batches, claiming several of them at once per bitmap word, and
+    /// enough buckets for the batch are reserved up front.
+    ///
+    /// It blocks in the same way as [`Arena::insert`] if the `Arena` already
+    /// has `self.limit()` buckets and all of them are full.
+    pub fn insert_many<I>(&self, values: I) -> Vec<ArenaArc<T, BITARRAY_LEN, LEN>>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let values = values.into_iter();
+        let mut arcs = Vec::with_capacity(values.size_hint().0);
+
+        self.insert_many_with(values, |arc| arcs.push(arc));
+
+        arcs
+    }
+
+    fn insert_many_with(
+        &self,
+        values: impl Iterator<Item = T>,
+        mut f: impl FnMut(ArenaArc<T, BITARRAY_LEN, LEN>),
+    ) {
+        let mut values = values.peekable();
+        let mut vacants = Vec::new();
+
+        while values.peek().is_some() {
+            self.reserve_slots(values.size_hint().0.max(1), &mut vacants);
+
+            // Slots left unused are freed when `Drain` drops them.
+            for vacant in vacants.drain(..) {
+                match values.next() {
+                    Some(value) => f(vacant.insert(value)),
+                    None => break,
                 }
             }
         }
+    }
+
+    /// Reserve at most `n` slots into `vacants`, which must be empty,
+    /// reserving more buckets if necessary.
+    ///
+    /// If the `Arena` already has `self.limit()` buckets, then only the slots
+    /// available are reserved, or block until one slot is freed if there is
+    /// none.
+    fn reserve_slots(&self, n: usize, vacants: &mut Vec<VacantSlot<T, BITARRAY_LEN, LEN>>) {
+        debug_assert!(vacants.is_empty());
 
-        // Slow path where `reserve` is used.
         loop {
-            match self.try_insert(value) {
-                Ok(arc) => break arc,
-                Err((val, len)) => {
-                    value = val;
-
-                    // If len == Self::max_buckets(), then we would have to
-                    // wait for slots to be removed from `Arena`.
-                    if len != Self::max_buckets() {
-                        self.reserve(len + 8);
-                    }
+            let len = self.try_reserve_slots(n - vacants.len(), vacants);
+
+            if vacants.len() == n {
+                break;
+            } else if len == self.limit {
+                if vacants.is_empty() {
+                    vacants.push(self.reserve_slot());
                 }
+                break;
+            } else {
+                // Reserve enough buckets for the rest of the batch at once.
+                let remaining = (n - vacants.len()).div_ceil(LEN);
+                let remaining = remaining.min(u32::MAX as usize) as u32;
+
+                self.reserve(len.saturating_add(remaining));
+            }
+        }
+    }
+
+    /// Same as [`Arena::insert`], except that it gives up and returns
+    /// the value back if no slot is freed within `timeout`.
+    ///
+    /// Only the waiting for a free slot is limited by `timeout`,
+    /// reserving new buckets is not.
+    pub fn insert_timeout(
+        &self,
+        value: T,
+        timeout: Duration,
+    ) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, T> {
+        // If the deadline cannot be represented, then it is effectively
+        // the same as waiting forever.
+        match self.reserve_slot_impl(Instant::now().checked_add(timeout)) {
+            Some(vacant) => Ok(vacant.insert(value)),
+            None => Err(value),
+        }
+    }
+
+    /// Same as [`Arena::insert`], except that it waits for a free slot
+    /// asynchronously instead of blocking the thread.
+    ///
+    /// The future is woken up by the thread that frees a slot, thus it
+    /// works with any async runtime.
+    #[cfg(feature = "async")]
+    pub async fn insert_async(&self, value: T) -> ArenaArc<T, BITARRAY_LEN, LEN> {
+        let vacant = match self.reserve_slot_or_grow() {
+            Some(vacant) => vacant,
+            None => {
+                let mut waiter = AsyncWaiter::new(&self.shared);
+                poll_fn(|cx| waiter.poll_slot(cx, || self.reserve_slot_or_grow())).await
+            }
+        };
+
+        vacant.insert(value)
+    }
+
+    fn reserve_slot_impl(
+        &self,
+        deadline: Option<Instant>,
+    ) -> Option<VacantSlot<T, BITARRAY_LEN, LEN>> {
+        self.reserve_slot_or_grow().or_else(|| {
+            // Wait for slots to be removed from `Arena`.
+            self.shared
+                .wait_for_slot(deadline, || self.reserve_slot_or_grow())
+        })
+    }
+
+    /// Reserve one slot, reserving more buckets if necessary.
+    ///
+    /// Return `None` if the `Arena` already has `self.limit()` buckets
+    /// and all of them are full.
+    pub(crate) fn reserve_slot_or_grow(&self) -> Option<VacantSlot<T, BITARRAY_LEN, LEN>> {
+        loop {
+            match self.try_reserve_slot() {
+                Ok(vacant) => break Some(vacant),
+                // We would have to wait for slots to be removed from `Arena`.
+                Err(len) if len == self.limit => break None,
+                // Grow by 1.5 exponential to have amoritized O(1), adding +4 more in case
+                // there's only one element (1 * 3 / 2 evaluaes to 1 in rust).
+                Err(len) => self.reserve(len.saturating_mul(3) / 2 + 4),
             }
         }
     }
@@ -198,42 +487,183 @@ impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Arena<T, BITAR
 
 type AccessOp<T, const BITARRAY_LEN: usize, const LEN: usize> =
     unsafe fn(
-        Arc<Bucket<T, BITARRAY_LEN, LEN>>,
+        &Arc<Bucket<T, BITARRAY_LEN, LEN>>,
         u32,
         u32,
-    ) -> Option<ArenaArc<T, BITARRAY_LEN, LEN>>;
+    ) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error>;
+
+fn access_in<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize>(
+    buckets: Slice<'_, Bucket<T, BITARRAY_LEN, LEN>>,
+    slot: u32,
+    op: AccessOp<T, BITARRAY_LEN, LEN>,
+) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error> {
+    let bucket_index = slot / (LEN as u32);
+    let index = slot % (LEN as u32);
+
+    buckets
+        .get(bucket_index as usize)
+        .ok_or(Error::OutOfRange)
+        // Safety: index is <= LEN
+        .and_then(|bucket| unsafe { op(&bucket, bucket_index, index) })
+}
 
 impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Arena<T, BITARRAY_LEN, LEN> {
     fn access_impl(
         &self,
         slot: u32,
         op: AccessOp<T, BITARRAY_LEN, LEN>,
-    ) -> Option<ArenaArc<T, BITARRAY_LEN, LEN>> {
-        let bucket_index = slot / (LEN as u32);
-        let index = slot % (LEN as u32);
-
-        self.buckets
-            .as_slice()
-            .get(bucket_index as usize)
-            .cloned()
-            // Safety: index is <= LEN
-            .and_then(|bucket| unsafe { op(bucket, bucket_index, index) })
+    ) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error> {
+        access_in(self.buckets.as_slice(), slot, op)
     }
 
+    /// Return `Error::OutOfRange` if `slot` is not in any bucket allocated,
+    /// `Error::Vacant` if there is no value in the slot or `Error::Removed`
+    /// if the value is already removed.
+    ///
     /// May enter busy loop if the slot is not fully initialized.
     ///
     /// This function is lock free.
-    pub fn remove(&self, slot: u32) -> Option<ArenaArc<T, BITARRAY_LEN, LEN>> {
+    pub fn remove(&self, slot: u32) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error> {
         self.access_impl(slot, Bucket::remove)
     }
 
+    /// Same as calling [`Arena::remove`] on every slot in `slots`, except
+    /// that the buckets are only loaded once for the whole batch.
+    ///
+    /// The results are in the same order as `slots`, if a slot is given
+    /// more than once then all but the first return `Error::Removed`.
+    ///
+    /// May enter busy loop if any of the slots is not fully initialized.
+    ///
+    /// This function is lock free.
+    pub fn remove_many(&self, slots: &[u32]) -> Vec<Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error>> {
+        let buckets = self.buckets.as_slice();
+
+        slots
+            .iter()
+            .map(|slot| access_in(buckets, *slot, Bucket::remove))
+            .collect()
+    }
+
+    /// Return `Error::OutOfRange` if `slot` is not in any bucket allocated,
+    /// `Error::Vacant` if there is no value in the slot or `Error::Removed`
+    /// if the value is already removed.
+    ///
     /// May enter busy loop if the slot is not fully initialized.
     ///
     /// This function is lock free.
-    pub fn get(&self, slot: u32) -> Option<ArenaArc<T, BITARRAY_LEN, LEN>> {
+    pub fn get(&self, slot: u32) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error> {
         self.access_impl(slot, Bucket::get)
     }
 
+    /// Same as [`Arena::remove`], except that `Error::Initializing` is
+    /// returned instead of entering busy loop if the slot is not fully
+    /// initialized.
+    ///
+    /// This function is lock free.
+    pub fn try_remove(&self, slot: u32) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error> {
+        self.access_impl(slot, Bucket::try_remove)
+    }
+
+    /// Same as [`Arena::get`], except that `Error::Initializing` is
+    /// returned instead of entering busy loop if the slot is not fully
+    /// initialized.
+    ///
+    /// This function is lock free.
+    pub fn try_get(&self, slot: u32) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error> {
+        self.access_impl(slot, Bucket::try_get)
+    }
+
+    /// Same as [`Arena::get`], except that `Error::Reused` is returned if the
+    /// slot has since been reused for another value.
+    ///
+    /// May enter busy loop if the slot is not fully initialized.
+    ///
+    /// This function is lock free.
+    pub fn get_by_key(&self, key: Key) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error> {
+        let arc = self.get(key.slot)?;
+
+        if ArenaArc::key(&arc).generation == key.generation {
+            Ok(arc)
+        } else {
+            Err(Error::Reused)
+        }
+    }
+
+    /// Same as [`Arena::remove`], except that nothing is removed and
+    /// `Error::Reused` is returned if the slot has since been reused for
+    /// another value.
+    ///
+    /// May enter busy loop if the slot is not fully initialized.
+    ///
+    /// This function is lock free.
+    pub fn remove_by_key(&self, key: Key) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error> {
+        // Hold a reference while checking the generation so that the slot
+        // cannot be reused before it is removed.
+        let arc = self.get_by_key(key)?;
+
+        if ArenaArc::remove(&arc) {
+            Ok(arc)
+        } else {
+            Err(Error::Removed)
+        }
+    }
+
+    /// Remove the element in `slot` and return its value if there is no other
+    /// `ArenaArc` to it.
+    ///
+    /// Otherwise, or if the slot cannot be removed as in [`Arena::remove`],
+    /// `None` is returned and the value is dropped along with the last
+    /// `ArenaArc`.
+    ///
+    /// May enter busy loop if the slot is not fully initialized.
+    pub fn take(&self, slot: u32) -> Option<T> {
+        self.remove(slot).ok().and_then(ArenaArc::into_inner)
+    }
+
+    /// Remove the element in `slot`, block until all other `ArenaArc`s to it
+    /// are dropped and then return its value.
+    ///
+    /// Return `None` if the slot cannot be removed as in [`Arena::remove`].
+    ///
+    /// It never returns if the calling thread holds an `ArenaArc` to the
+    /// element, or if any of them is leaked.
+    pub fn take_wait(&self, slot: u32) -> Option<T> {
+        let mut arc = self.remove(slot).ok()?;
+
+        loop {
+            arc = match ArenaArc::try_unwrap(arc) {
+                Ok(value) => break Some(value),
+                Err(arc) => arc,
+            };
+
+            // Unwrapping is retried instead of assuming that the strong count
+            // never increases again once it is observed to be 1.
+            self.shared
+                .wait_for_slot(None, || (ArenaArc::strong_count(&arc) == 1).then_some(()));
+        }
+    }
+
+    /// Return an iterator over all elements in the `Arena` that are
+    /// not removed.
+    ///
+    /// The iteration is weakly consistent: elements inserted or removed
+    /// while iterating may or may not be yielded, but every element yielded
+    /// was fully initialized and not removed at the time it was visited,
+    /// and no slot is yielded more than once.
+    ///
+    /// Slots that are still being initialized are skipped, thus
+    /// this function never enters busy loop.
+    ///
+    /// This function is lock free.
+    pub fn iter(&self) -> Iter<'_, T, BITARRAY_LEN, LEN> {
+        Iter {
+            arena: self,
+            bucket_index: 0,
+            bucket_iter: None,
+        }
+    }
+
     /// Return number of buckets allocated.
     ///
     /// This function is lock free.
@@ -245,6 +675,137 @@ impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Arena<T, BITAR
     pub fn is_empty(&self) -> bool {
         self.buckets.is_empty()
     }
+
+    /// Drop the trailing buckets that have no slot allocated, which includes
+    /// slots reserved by a [`VacantSlot`] or an [`ArenaBox`].
+    ///
+    /// The memory of a bucket is released once no
+    /// [`ArenaWeak`](crate::ArenaWeak) to it is alive.
+    ///
+    /// The slots in the buckets dropped are out of range until the `Arena`
+    /// grows again, and [`Key`]s to them never match any new value.
+    ///
+    /// It requires exclusive access, so an `Arena` shared by an `Arc` can
+    /// only be shrunk once it is unwrapped, e.g. by `Arc::get_mut`.
+    ///
+    /// Every other method reads the buckets without touching their reference
+    /// counts to stay lock free, thus a bucket can only be dropped once no
+    /// other thread can access the `Arena`, which `&mut self` guarantees
+    /// along with no insertion into a bucket while it is being checked.
+    /// Shrinking through `&self` would require deferring the drop of the
+    /// buckets, e.g. with epochs, which every access would have to pay for.
+    pub fn shrink_to_fit(&mut self) {
+        let slice = self.buckets.as_slice();
+        let new_len = (0..slice.len())
+            .rev()
+            .find(|i| !slice.get(*i).is_some_and(|bucket| bucket.try_retire()))
+            .map_or(0, |i| i + 1);
+
+        self.buckets.truncate(new_len);
+    }
+
+    /// Return the number of slots in the buckets allocated.
+    ///
+    /// This function is lock free.
+    pub fn capacity(&self) -> u32 {
+        self.len() * (LEN as u32)
+    }
+
+    /// Return the number of elements that are not removed.
+    ///
+    /// The count is only a snapshot, elements inserted or removed
+    /// concurrently may or may not be counted.
+    ///
+    /// This function is lock free.
+    pub fn count(&self) -> u32 {
+        self.buckets
+            .as_slice()
+            .iter()
+            .map(|bucket| bucket.live_count())
+            .sum()
+    }
+
+    /// Return the number of elements that are removed, but are still
+    /// kept alive by an `ArenaArc`.
+    ///
+    /// The count is only a snapshot in the same way as [`Arena::count`].
+    ///
+    /// This function is lock free.
+    pub fn zombie_count(&self) -> u32 {
+        self.buckets
+            .as_slice()
+            .iter()
+            .map(|bucket| bucket.zombie_count())
+            .sum()
+    }
+
+    /// Return a snapshot of the occupancy of every bucket.
+    ///
+    /// This function is lock free.
+    pub fn occupancy(&self) -> Vec<BucketStats> {
+        self.buckets
+            .as_slice()
+            .iter()
+            .map(|bucket| bucket.stats())
+            .collect()
+    }
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Extend<T>
+    for Arena<T, BITARRAY_LEN, LEN>
+{
+    /// Insert all values in the same way as [`Arena::insert_many`], dropping
+    /// the `ArenaArc`s returned.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+        self.insert_many_with(values.into_iter(), drop);
+    }
+}
+
+impl<'a, T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> IntoIterator
+    for &'a Arena<T, BITARRAY_LEN, LEN>
+{
+    type Item = ArenaArc<T, BITARRAY_LEN, LEN>;
+    type IntoIter = Iter<'a, T, BITARRAY_LEN, LEN>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator returned by [`Arena::iter`].
+///
+/// Buckets are visited in order and the bucket array is reloaded
+/// when moving on to the next bucket, so buckets allocated during the
+/// iteration are visited as well.
+#[derive(Debug)]
+pub struct Iter<'a, T, const BITARRAY_LEN: usize, const LEN: usize> {
+    arena: &'a Arena<T, BITARRAY_LEN, LEN>,
+    bucket_index: u32,
+    bucket_iter: Option<bucket::Iter<T, BITARRAY_LEN, LEN>>,
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Iterator
+    for Iter<'_, T, BITARRAY_LEN, LEN>
+{
+    type Item = ArenaArc<T, BITARRAY_LEN, LEN>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(arc) = self.bucket_iter.as_mut().and_then(Iterator::next) {
+                break Some(arc);
+            }
+
+            let bucket = self
+                .arena
+                .buckets
+                .as_slice()
+                .get(self.bucket_index as usize)
+                .map(|bucket| Arc::clone(&bucket))?;
+
+            self.bucket_iter = Some(bucket::Iter::new(bucket, self.bucket_index));
+            self.bucket_index += 1;
+        }
+    }
 }
 
 #[cfg(test)]
@@ -266,6 +827,687 @@ mod tests {
         assert_eq!(ArenaArc::slot(&arena.remove(slot).unwrap()), slot);
     }
 
+    #[test]
+    fn test_key() {
+        let arena: Arena<_, 1, { LEN }> = Arena::with_capacity(1);
+
+        let arc = arena.insert(0_u32);
+        let key = ArenaArc::key(&arc);
+        assert_eq!(key.slot, ArenaArc::slot(&arc));
+
+        assert_eq!(*arena.get_by_key(key).unwrap(), 0);
+        assert_eq!(
+            arena
+                .get_by_key(Key {
+                    generation: key.generation.wrapping_add(1),
+                    ..key
+                })
+                .unwrap_err(),
+            Error::Reused
+        );
+
+        assert_eq!(*arena.remove_by_key(key).unwrap(), 0);
+        assert_eq!(arena.remove_by_key(key).unwrap_err(), Error::Removed);
+        drop(arc);
+
+        // Fill the only bucket so that the slot gets reused.
+        let arcs: Vec<_> = (1..=LEN as u32).map(|i| arena.insert(i)).collect();
+        let arc = arcs
+            .iter()
+            .find(|arc| ArenaArc::slot(arc) == key.slot)
+            .unwrap();
+        assert_ne!(ArenaArc::key(arc), key);
+
+        assert_eq!(arena.get_by_key(key).unwrap_err(), Error::Reused);
+        assert_eq!(arena.remove_by_key(key).unwrap_err(), Error::Reused);
+        assert!(!ArenaArc::is_removed(arc));
+        assert_eq!(*arena.get_by_key(ArenaArc::key(arc)).unwrap(), **arc);
+    }
+
+    #[test]
+    fn test_access_error() {
+        let arena: Arena<_, 1, { LEN }> = Arena::with_capacity(1);
+
+        assert_eq!(arena.get(LEN as u32).unwrap_err(), Error::OutOfRange);
+        assert_eq!(arena.remove(u32::MAX).unwrap_err(), Error::OutOfRange);
+
+        let arc = arena.insert(1);
+        let slot = ArenaArc::slot(&arc);
+        let vacant = (slot + 1) % LEN as u32;
+
+        assert_eq!(arena.get(vacant).unwrap_err(), Error::Vacant);
+        assert_eq!(arena.remove(vacant).unwrap_err(), Error::Vacant);
+
+        assert_eq!(*arena.remove(slot).unwrap(), 1);
+        assert_eq!(arena.get(slot).unwrap_err(), Error::Removed);
+        assert_eq!(arena.remove(slot).unwrap_err(), Error::Removed);
+
+        drop(arc);
+        assert_eq!(arena.get(slot).unwrap_err(), Error::Vacant);
+        assert_eq!(arena.try_get(slot).unwrap_err(), Error::Vacant);
+        assert_eq!(arena.try_remove(slot).unwrap_err(), Error::Vacant);
+
+        let arc = arena.insert(2);
+        let slot = ArenaArc::slot(&arc);
+        assert_eq!(*arena.try_get(slot).unwrap(), 2);
+        assert_eq!(*arena.try_remove(slot).unwrap(), 2);
+        assert_eq!(arena.try_get(slot).unwrap_err(), Error::Removed);
+        assert_eq!(arena.try_remove(slot).unwrap_err(), Error::Removed);
+    }
+
+    #[test]
+    fn test_insert_many() {
+        let arena: Arena<_, 1, { LEN }> = Arena::with_capacity(0);
+
+        let n = 3 * LEN as u32 + 5;
+        let arcs = arena.insert_many(0..n);
+        assert_eq!(arena.len(), 4);
+        assert_eq!(arcs.len(), n as usize);
+
+        for (i, arc) in arcs.iter().enumerate() {
+            assert_eq!(**arc, i as u32);
+            assert_eq!(*arena.get(ArenaArc::slot(arc)).unwrap(), i as u32);
+        }
+
+        // Slots reserved for the batch but left unused are freed.
+        let mut arena: Arena<_, 1, { LEN }> = Arena::with_limit(1, 1);
+        arena.extend((0..5).filter(|i| i % 2 == 0));
+        assert_eq!(arena.iter().count(), 3);
+        assert_eq!(arena.insert_many(3..LEN as u32).len(), LEN - 3);
+
+        let results = arena.remove_many(&[1, LEN as u32, 2, 1]);
+        assert_eq!(ArenaArc::slot(results[0].as_ref().unwrap()), 1);
+        assert_eq!(results[1].as_ref().unwrap_err(), &Error::OutOfRange);
+        assert_eq!(ArenaArc::slot(results[2].as_ref().unwrap()), 2);
+        assert_eq!(results[3].as_ref().unwrap_err(), &Error::Removed);
+        assert_eq!(arena.iter().count(), LEN - 2);
+    }
+
+    #[test]
+    fn test_try_insert_at() {
+        let arena: Arena<_, 1, { LEN }> = Arena::with_limit(0, 4);
+
+        let slot = 2 * LEN as u32 + 3;
+        let arc = arena.try_insert_at(slot, 1).unwrap();
+        assert_eq!(ArenaArc::slot(&arc), slot);
+        assert_eq!(arena.len(), 3);
+        assert_eq!(*arena.get(slot).unwrap(), 1);
+
+        assert_eq!(arena.try_insert_at(slot, 2).unwrap_err(), 2);
+        assert_eq!(arena.try_insert_at(4 * LEN as u32, 3).unwrap_err(), 3);
+        assert_eq!(arena.len(), 3);
+
+        let vacant = arena.reserve_slot();
+        assert_eq!(arena.try_insert_at(vacant.slot(), 4).unwrap_err(), 4);
+        drop(vacant);
+
+        // The slot is freed once the last reference is dropped.
+        ArenaArc::remove(&arc);
+        assert_eq!(arena.try_insert_at(slot, 5).unwrap_err(), 5);
+        drop(arc);
+        assert_eq!(*arena.try_insert_at(slot, 6).unwrap(), 6);
+    }
+
+    #[test]
+    fn test_take() {
+        use std::thread::{sleep, spawn};
+        use std::time::Duration;
+
+        let arena: Arena<_, 1, { LEN }> = Arena::with_capacity(1);
+
+        let slot = ArenaArc::slot(&arena.insert("a".to_string()));
+        assert_eq!(arena.take(slot).unwrap(), "a");
+        assert_eq!(arena.take(slot), None);
+        assert_eq!(arena.get(slot).unwrap_err(), Error::Vacant);
+
+        let arc = arena.insert("b".to_string());
+        let slot = ArenaArc::slot(&arc);
+        assert_eq!(arena.take(slot), None);
+        assert_eq!(arena.get(slot).unwrap_err(), Error::Removed);
+        drop(arc);
+        assert_eq!(arena.get(slot).unwrap_err(), Error::Vacant);
+
+        let arc = arena.insert("c".to_string());
+        let slot = ArenaArc::slot(&arc);
+        let cloned = arc.clone();
+
+        let handle = spawn(move || {
+            sleep(Duration::from_millis(50));
+            drop(arc);
+            sleep(Duration::from_millis(50));
+            drop(cloned);
+        });
+
+        assert_eq!(arena.take_wait(slot).unwrap(), "c");
+        assert_eq!(arena.take_wait(slot), None);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_take_wait_while_upgrading() {
+        use std::sync::Barrier;
+        use std::thread::{scope, yield_now};
+
+        let arena: Arena<_, 1, { LEN }> = Arena::with_capacity(1);
+
+        for i in 0..100 {
+            let arc = arena.insert(i);
+            let slot = ArenaArc::slot(&arc);
+            let weak = ArenaArc::downgrade(&arc);
+            drop(arc);
+
+            let barrier = Barrier::new(5);
+
+            scope(|s| {
+                for _ in 0..4 {
+                    s.spawn(|| {
+                        barrier.wait();
+                        while let Some(arc) = ArenaWeak::upgrade(&weak) {
+                            yield_now();
+                            drop(arc);
+                        }
+                    });
+                }
+
+                barrier.wait();
+                assert_eq!(arena.take_wait(slot), Some(i));
+            });
+        }
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let arena: Arena<_, 1, { LEN }> = Arena::with_capacity(1);
+
+        let mut arc = arena.insert(1);
+        assert!(ArenaArc::get_mut(&mut arc).is_none());
+
+        ArenaArc::remove(&arc);
+        let cloned = arc.clone();
+        assert!(ArenaArc::get_mut(&mut arc).is_none());
+        drop(cloned);
+
+        let key = ArenaArc::key(&arc);
+        *ArenaArc::get_mut(&mut arc).unwrap() = 2;
+        assert_eq!(*arc, 2);
+        assert_eq!(ArenaArc::key(&arc), key);
+
+        // Not unique, so the value is cloned.
+        let mut arc = arena.insert(3);
+        let slot = ArenaArc::slot(&arc);
+        *ArenaArc::make_mut(&mut arc, &arena).unwrap() += 1;
+        assert_eq!(*arc, 4);
+        assert_ne!(ArenaArc::slot(&arc), slot);
+        assert_eq!(*arena.get(slot).unwrap(), 3);
+
+        // Already unique, so it is mutated in place.
+        let slot = ArenaArc::slot(&arc);
+        *ArenaArc::make_mut(&mut arc, &arena).unwrap() += 1;
+        assert_eq!(*arc, 5);
+        assert_eq!(ArenaArc::slot(&arc), slot);
+        assert_eq!(arena.get(slot).unwrap_err(), Error::Removed);
+
+        // No slot left to clone the value into.
+        let arena: Arena<_, 1, { LEN }> = Arena::with_limit(1, 1);
+        let mut arcs: Vec<_> = (0..LEN).map(|i| arena.insert(i)).collect();
+        assert!(ArenaArc::make_mut(&mut arcs[0], &arena).is_none());
+        assert_eq!(*arcs[0], 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "another Arena")]
+    fn test_make_mut_another_arena() {
+        let arena: Arena<_, 1, { LEN }> = Arena::with_capacity(1);
+        let other: Arena<_, 1, { LEN }> = Arena::with_capacity(1);
+
+        let mut arc = arena.insert(1);
+        ArenaArc::make_mut(&mut arc, &other);
+    }
+
+    #[test]
+    fn test_insert_unique() {
+        let arena: Arena<_, 1, { LEN }> = Arena::with_limit(1, 1);
+
+        let mut boxed = arena.insert_unique(vec![1]);
+        let slot = ArenaBox::slot(&boxed);
+        let key = ArenaBox::key(&boxed);
+        boxed.push(2);
+
+        assert_eq!(arena.get(slot).unwrap_err(), Error::Vacant);
+        assert_eq!(arena.iter().count(), 0);
+
+        let arc = ArenaBox::into_shared(boxed);
+        assert_eq!(ArenaArc::key(&arc), key);
+        assert_eq!(ArenaArc::strong_count(&arc), 2);
+        assert_eq!(*arena.get(slot).unwrap(), [1, 2]);
+
+        // Dropping an `ArenaBox` frees the slot.
+        let boxes: Vec<_> = (1..LEN).map(|i| arena.insert_unique(vec![i])).collect();
+        assert!(arena.try_insert(vec![0]).is_err());
+        drop(boxes);
+        assert_eq!(arena.insert_many((1..LEN).map(|i| vec![i])).len(), LEN - 1);
+    }
+
+    #[test]
+    fn test_full_buckets() {
+        let arena: Arena<_, 1, { LEN }> = Arena::with_limit(3, 3);
+
+        let mut arcs: Vec<_> = (0..3 * LEN).map(|i| arena.insert(i)).collect();
+        assert!(matches!(
+            arena.try_insert(0),
+            Err(InsertError::CapacityExhausted { .. })
+        ));
+        assert_eq!(arena.shared.full_buckets.not_full(0, 3).count(), 0);
+
+        let arc = arcs.swap_remove(LEN + 1);
+        let slot = ArenaArc::slot(&arc);
+        assert!(ArenaArc::remove(&arc));
+        drop(arc);
+
+        let bucket_index = (slot / LEN as u32) as usize;
+        assert!(arena.shared.full_buckets.not_full(0, 3).eq([bucket_index]));
+        assert_eq!(ArenaArc::slot(&arena.try_insert(0).unwrap()), slot);
+    }
+
+    #[test]
+    fn test_insert_wait_while_freeing() {
+        use std::thread::{scope, yield_now};
+
+        let arena: Arena<_, 1, { LEN }> = Arena::with_limit(1, 1);
+
+        // Leave only one slot for all the threads below, so that most of
+        // their insertions wait for it to be freed.
+        let _arcs: Vec<_> = (0..LEN - 1).map(|i| arena.insert(i)).collect();
+
+        scope(|s| {
+            for _ in 0..16 {
+                s.spawn(|| {
+                    for i in 0..500 {
+                        let arc = arena.insert(i);
+                        // Let other threads try to insert while the slot
+                        // is taken.
+                        yield_now();
+                        assert!(ArenaArc::remove(&arc));
+                        drop(arc);
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_cursor() {
+        let arena: Arena<_, 1, { LEN }> = Arena::with_limit(2, 2);
+        let bucket_index = |arc: &ArenaArc<_, 1, { LEN }>| ArenaArc::slot(arc) / LEN as u32;
+
+        let arcs: Vec<_> = (0..LEN).map(|i| arena.insert(i)).collect();
+        let first = bucket_index(&arcs[0]);
+        assert!(arcs.iter().all(|arc| bucket_index(arc) == first));
+
+        let arc = arena.insert(LEN);
+        assert_ne!(bucket_index(&arc), first);
+
+        // Stay in the same bucket even if the first one has room again.
+        assert!(ArenaArc::remove(&arcs[0]));
+        drop(arcs);
+        assert_eq!(bucket_index(&arena.insert(0)), bucket_index(&arc));
+    }
+
+    #[test]
+    fn test_stats() {
+        let arena: Arena<_, 1, { LEN }> = Arena::with_capacity(2);
+        assert_eq!(arena.capacity(), 2 * LEN as u32);
+        assert_eq!(arena.count(), 0);
+        assert_eq!(arena.zombie_count(), 0);
+
+        let arcs = arena.insert_many(0..LEN as u32 + 3);
+        let vacant = arena.reserve_slot();
+        assert_eq!(arena.count(), LEN as u32 + 3);
+
+        arena.remove(ArenaArc::slot(&arcs[0])).unwrap();
+        ArenaArc::remove(&arcs[1]);
+        assert_eq!(arena.take(ArenaArc::slot(&arcs[2])), None);
+        assert_eq!(arena.count(), LEN as u32);
+        assert_eq!(arena.zombie_count(), 3);
+
+        let occupancy = arena.occupancy();
+        assert_eq!(occupancy.len(), 2);
+        let sum = |f: fn(&BucketStats) -> u32| occupancy.iter().map(f).sum::<u32>();
+        assert_eq!(sum(|stats| stats.live), LEN as u32);
+        assert_eq!(sum(|stats| stats.zombies), 3);
+        assert_eq!(sum(|stats| stats.allocated), LEN as u32 + 4);
+
+        drop(vacant);
+        drop(arcs);
+        assert_eq!(arena.count(), LEN as u32);
+        assert_eq!(arena.zombie_count(), 0);
+        assert_eq!(
+            arena
+                .occupancy()
+                .iter()
+                .map(|stats| stats.allocated)
+                .sum::<u32>(),
+            LEN as u32
+        );
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let mut arena: Arena<_, 1, { LEN }> = Arena::with_limit(0, 3);
+
+        let a = arena.try_insert_at(0, 0).unwrap();
+        let b = arena.try_insert_at(2 * LEN as u32 + 1, 1).unwrap();
+        let key = ArenaArc::key(&b);
+        assert_eq!(arena.len(), 3);
+
+        arena.shrink_to_fit();
+        assert_eq!(arena.len(), 3);
+
+        // Still referenced after being removed.
+        ArenaArc::remove(&b);
+        arena.shrink_to_fit();
+        assert_eq!(arena.len(), 3);
+
+        drop(b);
+        arena.shrink_to_fit();
+        assert_eq!(arena.len(), 1);
+        assert_eq!(arena.capacity(), LEN as u32);
+        assert_eq!(arena.get(key.slot).unwrap_err(), Error::OutOfRange);
+
+        // The slot is reused in a new bucket.
+        let c = arena.try_insert_at(key.slot, 2).unwrap();
+        assert_eq!(arena.len(), 3);
+        assert!(ArenaArc::key(&c).generation > key.generation);
+        assert_eq!(arena.get_by_key(key).unwrap_err(), Error::Reused);
+
+        // Reserved slots are not freed either.
+        let vacant = arena.reserve_slot();
+        assert_eq!(arena.take(0), None);
+        drop(a);
+        arena.remove(key.slot).unwrap();
+        drop(c);
+        arena.shrink_to_fit();
+        assert_eq!(arena.len(), vacant.slot() / LEN as u32 + 1);
+
+        drop(vacant);
+        arena.shrink_to_fit();
+        assert!(arena.is_empty());
+        assert_eq!(*arena.insert(3), 3);
+    }
+
+    #[test]
+    fn test_reserve_slot() {
+        let arena: Arena<_, 1, { LEN }> = Arena::with_limit(1, 1);
+
+        let vacant = arena.reserve_slot();
+        let slot = vacant.slot();
+        assert_eq!(vacant.key().slot, slot);
+
+        assert_eq!(arena.get(slot).unwrap_err(), Error::Vacant);
+        assert_eq!(arena.try_get(slot).unwrap_err(), Error::Vacant);
+        assert_eq!(arena.remove(slot).unwrap_err(), Error::Vacant);
+        assert_eq!(arena.iter().count(), 0);
+
+        let key = vacant.key();
+        let arc = vacant.insert(slot);
+        assert_eq!(ArenaArc::key(&arc), key);
+        assert_eq!(*arena.get_by_key(key).unwrap(), slot);
+
+        // Dropping a `VacantSlot` frees the slot.
+        let vacants: Vec<_> = (1..LEN).map(|_| arena.reserve_slot()).collect();
+        assert_eq!(
+            arena.try_insert(0).unwrap_err(),
+            InsertError::CapacityExhausted { value: 0 }
+        );
+        drop(vacants);
+
+        let arcs: Vec<_> = (1..LEN).map(|_| arena.insert_with(|slot| slot)).collect();
+        for arc in &arcs {
+            assert_eq!(**arc, ArenaArc::slot(arc));
+        }
+    }
+
+    #[test]
+    fn test_try_insert() {
+        let arena: Arena<_, 1, { LEN }> = Arena::with_capacity(0);
+
+        match arena.try_insert(0).unwrap_err() {
+            InsertError::Full { value, len } => assert_eq!((value, len), (0, 0)),
+            err => panic!("Unexpected error {:?}", err),
+        }
+
+        arena.reserve(1);
+        let _arcs: Vec<_> = (0..LEN).map(|i| arena.try_insert(i).unwrap()).collect();
+
+        let err = arena.try_insert(LEN).unwrap_err();
+        assert!(matches!(err, InsertError::Full { len: 1, .. }));
+        assert_eq!(err.into_value(), LEN);
+
+        let arena: Arena<_, 1, { LEN }> = Arena::with_limit(1, 1);
+        let _arcs: Vec<_> = (0..LEN).map(|i| arena.try_insert(i).unwrap()).collect();
+        assert!(matches!(
+            arena.try_insert(LEN).unwrap_err(),
+            InsertError::CapacityExhausted { value: LEN }
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "limit of 0")]
+    fn test_zero_limit() {
+        let _arena: Arena<u32, 1, { LEN }> = Arena::with_limit(0, 0);
+    }
+
+    #[test]
+    fn test_insert_timeout() {
+        use std::time::Duration;
+
+        let arena: Arena<_, 1, { LEN }> = Arena::with_capacity(0);
+
+        let arc = arena.insert_timeout(1, Duration::from_secs(0)).unwrap();
+        assert_eq!(*arc, 1);
+        assert_eq!(*arena.get(ArenaArc::slot(&arc)).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_insert_wait() {
+        use std::thread::{sleep, spawn};
+        use std::time::Duration;
+
+        let arena: Arena<_, 1, { LEN }> = Arena::with_limit(1, 1);
+
+        let arcs: Vec<_> = (0..LEN).map(|i| arena.insert(i)).collect();
+        assert_eq!(arena.len(), 1);
+
+        // No slot is freed before the deadline.
+        assert_eq!(
+            arena
+                .insert_timeout(LEN, Duration::from_millis(10))
+                .unwrap_err(),
+            LEN
+        );
+
+        let arc = arcs[0].clone();
+        assert!(ArenaArc::remove(&arc));
+        drop(arcs);
+
+        // The slot is only freed once the last `ArenaArc` is dropped.
+        let handle = spawn(move || {
+            sleep(Duration::from_millis(10));
+            drop(arc);
+        });
+
+        assert_eq!(*arena.insert(LEN), LEN);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_limit() {
+        use std::thread::{sleep, spawn};
+        use std::time::Duration;
+
+        let arena: Arena<_, 1, { LEN }> = Arena::with_limit(0, 1);
+        assert_eq!(arena.limit(), 1);
+
+        let arcs: Vec<_> = (0..LEN).map(|i| arena.insert(i)).collect();
+        assert_eq!(arena.len(), 1);
+
+        arena.reserve(2);
+        assert!(arena.try_reserve(2));
+        assert_eq!(arena.len(), 1);
+
+        assert_eq!(
+            arena
+                .insert_timeout(LEN, Duration::from_millis(10))
+                .unwrap_err(),
+            LEN
+        );
+        match arena.try_insert(LEN).unwrap_err() {
+            InsertError::CapacityExhausted { value } => assert_eq!(value, LEN),
+            err => panic!("Unexpected error {:?}", err),
+        }
+
+        let arc = arcs[0].clone();
+        assert!(ArenaArc::remove(&arc));
+        drop(arcs);
+
+        let handle = spawn(move || {
+            sleep(Duration::from_millis(10));
+            drop(arc);
+        });
+
+        assert_eq!(*arena.insert(LEN), LEN);
+        handle.join().unwrap();
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_insert_async() {
+        use std::future::Future;
+        use std::pin::pin;
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+        use std::thread::{self, sleep, spawn, Thread};
+        use std::time::Duration;
+
+        struct ThreadWaker(Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        fn block_on<F: Future>(fut: F) -> F::Output {
+            let mut fut = pin!(fut);
+            let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+            let mut cx = Context::from_waker(&waker);
+
+            loop {
+                match fut.as_mut().poll(&mut cx) {
+                    Poll::Ready(output) => break output,
+                    Poll::Pending => thread::park(),
+                }
+            }
+        }
+
+        fn assert_send<T: Send>(_: &T) {}
+
+        let arena: Arena<_, 1, { LEN }> = Arena::with_limit(0, 1);
+
+        let arcs: Vec<_> = (0..LEN).map(|i| block_on(arena.insert_async(i))).collect();
+        assert_eq!(arena.len(), 1);
+
+        let arc = arcs[0].clone();
+        assert!(ArenaArc::remove(&arc));
+        drop(arcs);
+
+        let handle = spawn(move || {
+            sleep(Duration::from_millis(10));
+            drop(arc);
+        });
+
+        let fut = arena.insert_async(LEN);
+        assert_send(&fut);
+        assert_eq!(*block_on(fut), LEN);
+
+        handle.join().unwrap();
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_insert_async_cancel() {
+        use std::future::Future;
+        use std::pin::pin;
+        use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+
+        /// Count the wakeups of all futures.
+        struct CountWaker(Arc<AtomicUsize>);
+
+        impl Wake for CountWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Relaxed);
+            }
+        }
+
+        let arena: Arena<_, 1, { LEN }> = Arena::with_limit(1, 1);
+        let arcs: Vec<_> = (0..LEN).map(|i| arena.insert(i)).collect();
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let new_waker = || Waker::from(Arc::new(CountWaker(count.clone())));
+
+        // Cancel pending futures, e.g. as a timeout does, each polled
+        // by its own task.
+        for i in 0..100 {
+            let waker = new_waker();
+            let mut fut = pin!(arena.insert_async(i));
+            assert!(fut
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_pending());
+        }
+
+        let waker = new_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut fut = pin!(arena.insert_async(LEN));
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+
+        // Only the future still pending is woken up.
+        assert!(ArenaArc::remove(&arcs[0]));
+        drop(arcs);
+        assert_eq!(count.load(Relaxed), 1);
+        assert!(matches!(fut.poll(&mut cx), Poll::Ready(arc) if *arc == LEN));
+    }
+
+    #[test]
+    fn test_iter() {
+        let arena: Arena<_, 1, { LEN }> = Arena::with_capacity(0);
+        assert_eq!(arena.iter().count(), 0);
+
+        let arcs: Vec<_> = (0..(LEN as u32 * 3)).map(|i| arena.insert(i)).collect();
+
+        let mut values: Vec<_> = arena.iter().map(|arc| *arc).collect();
+        values.sort_unstable();
+        assert!(values.iter().copied().eq(0..(LEN as u32 * 3)));
+
+        for arc in arcs.iter().filter(|arc| ***arc % 2 == 0) {
+            ArenaArc::remove(arc);
+        }
+
+        let mut values: Vec<_> = (&arena).into_iter().map(|arc| *arc).collect();
+        values.sort_unstable();
+        assert!(values
+            .iter()
+            .copied()
+            .eq((0..(LEN as u32 * 3)).filter(|i| i % 2 != 0)));
+
+        for arc in arena.iter() {
+            assert!(!ArenaArc::is_removed(&arc));
+            assert_eq!(ArenaArc::slot(&arc), ArenaArc::slot(&arcs[*arc as usize]));
+        }
+    }
+
     /// Thread sanitizer produces false positive in this test.
     ///
     /// This has been discussed in
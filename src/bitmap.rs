@@ -1,8 +1,8 @@
 use super::{thread_id::get_thread_id, SliceExt};
 
-use std::{
-    array,
-    sync::atomic::{AtomicUsize, Ordering::Relaxed},
+use std::sync::atomic::{
+    AtomicUsize,
+    Ordering::{Acquire, Relaxed, SeqCst},
 };
 
 fn compare_exchange(atomic: &AtomicUsize, curr: usize, new: usize) -> Result<(), usize> {
@@ -16,8 +16,10 @@ fn compare_exchange(atomic: &AtomicUsize, curr: usize, new: usize) -> Result<(),
 pub(crate) struct BitMap<const BITARRAY_LEN: usize>([AtomicUsize; BITARRAY_LEN]);
 
 impl<const BITARRAY_LEN: usize> BitMap<BITARRAY_LEN> {
+    /// Buckets create their `BitMap` in place by zeroing it instead.
+    #[cfg(test)]
     pub(crate) fn new() -> Self {
-        Self(array::from_fn(|_| AtomicUsize::new(0)))
+        Self(std::array::from_fn(|_| AtomicUsize::new(0)))
     }
 
     /// # Safety
@@ -31,14 +33,44 @@ impl<const BITARRAY_LEN: usize> BitMap<BITARRAY_LEN> {
         (self.0.get_unchecked_on_release(offset).load(Relaxed) & mask) != 0
     }
 
-    pub(crate) fn allocate(&self) -> Option<usize> {
-        let bits = usize::BITS as usize;
+    /// # Safety
+    ///
+    /// `offset` < `BITARRAY_LEN`
+    pub(crate) unsafe fn load_chunk(&self, offset: usize) -> usize {
+        self.0.get_unchecked_on_release(offset).load(Relaxed)
+    }
+
+    /// Return the number of bits allocated.
+    pub(crate) fn count_ones(&self) -> u32 {
+        self.0
+            .iter()
+            .map(|chunk| chunk.load(Relaxed).count_ones())
+            .sum()
+    }
 
-        let mut pos = if BITARRAY_LEN == bits {
+    fn start_pos() -> usize {
+        if BITARRAY_LEN == usize::BITS as usize {
             0
         } else {
             get_thread_id() % BITARRAY_LEN
-        };
+        }
+    }
+
+    /// Allocate the first zero bit found, starting at a per-thread position.
+    pub(crate) fn allocate(&self) -> Option<usize> {
+        self.allocate_from(Self::start_pos())
+    }
+
+    /// Allocate the first zero bit found, starting at the `usize` at
+    /// `start % BITARRAY_LEN`.
+    ///
+    /// The bit is claimed with `fetch_or`, so that contending threads only
+    /// need to retry if they pick the same bit, instead of whenever the
+    /// `usize` changes.
+    pub(crate) fn allocate_from(&self, start: usize) -> Option<usize> {
+        let bits = usize::BITS as usize;
+
+        let mut pos = start % BITARRAY_LEN;
 
         let slice1_iter = self.0[pos..].iter();
         let slice2_iter = self.0[..pos].iter();
@@ -46,36 +78,99 @@ impl<const BITARRAY_LEN: usize> BitMap<BITARRAY_LEN> {
         for chunk in slice1_iter.chain(slice2_iter) {
             let mut value = chunk.load(Relaxed);
 
-            loop {
-                if value == usize::MAX {
-                    break;
+            while value != usize::MAX {
+                let i = value.trailing_ones() as usize;
+                let mask = 1 << i;
+
+                let prev = chunk.fetch_or(mask, Relaxed);
+                if (prev & mask) == 0 {
+                    return Some(pos * bits + i);
                 }
 
-                for i in 0..bits {
-                    let mask = 1 << i;
-                    if (value & mask) != 0 {
-                        continue;
-                    }
+                // Another thread has taken the bit, try again with the
+                // fresh value.
+                value = prev;
+            }
 
-                    match compare_exchange(chunk, value, value | mask) {
-                        Ok(_) => {
-                            return Some(pos * bits + i);
-                        }
-                        Err(new_value) => {
-                            value = new_value;
-                            // try again
-                            break;
+            pos = (pos + 1) % BITARRAY_LEN;
+        }
+
+        None
+    }
+
+    /// Allocate the bit at `index`, return false if it is already allocated.
+    ///
+    /// # Safety
+    ///
+    /// `index` < `BITARRAY_LEN * usize::BITS`
+    pub(crate) unsafe fn allocate_at(&self, index: usize) -> bool {
+        let bits = usize::BITS as usize;
+        let mask = 1 << (index % bits);
+        let offset = index / bits;
+
+        (self
+            .0
+            .get_unchecked_on_release(offset)
+            .fetch_or(mask, Relaxed)
+            & mask)
+            == 0
+    }
+
+    /// Allocate at most `n` bits and call `f` with the index of each bit
+    /// allocated, return the number of bits allocated.
+    ///
+    /// As many bits as needed are claimed from each `usize` in one CAS.
+    pub(crate) fn allocate_many(&self, n: usize, mut f: impl FnMut(usize)) -> usize {
+        let bits = usize::BITS as usize;
+
+        let mut pos = Self::start_pos();
+        let mut allocated = 0;
+
+        let slice1_iter = self.0[pos..].iter();
+        let slice2_iter = self.0[..pos].iter();
+
+        for chunk in slice1_iter.chain(slice2_iter) {
+            if allocated == n {
+                break;
+            }
+
+            let mut value = chunk.load(Relaxed);
+
+            while value != usize::MAX {
+                // Pick the lowest `n - allocated` zero bits.
+                let mut free = !value;
+                let mut mask = 0;
+                for _ in 0..(n - allocated).min(free.count_ones() as usize) {
+                    let lowest = free & free.wrapping_neg();
+                    mask |= lowest;
+                    free ^= lowest;
+                }
+
+                match compare_exchange(chunk, value, value | mask) {
+                    Ok(_) => {
+                        allocated += mask.count_ones() as usize;
+
+                        while mask != 0 {
+                            f(pos * bits + mask.trailing_zeros() as usize);
+                            mask &= mask - 1;
                         }
+
+                        break;
                     }
+                    // try again
+                    Err(new_value) => value = new_value,
                 }
             }
 
             pos = (pos + 1) % BITARRAY_LEN;
         }
 
-        None
+        allocated
     }
 
+    /// Use `SeqCst` so that a thread waiting for a free slot cannot
+    /// miss the deallocation, see `Shared::notify_slot_freed`.
+    ///
     /// # Safety
     ///
     /// `index` <= `BITARRAY_LEN / usize::BITS`
@@ -85,12 +180,22 @@ impl<const BITARRAY_LEN: usize> BitMap<BITARRAY_LEN> {
         let chunk = self.0.get_unchecked_on_release(index / bits);
         let mask = !(1 << (index % bits));
 
-        chunk.fetch_and(mask, Relaxed);
+        chunk.fetch_and(mask, SeqCst);
     }
 
-    #[cfg(test)]
+    /// Return true if no bit is allocated.
+    ///
+    /// Use `Acquire` so that it synchronizes with `BitMap::deallocate`.
+    pub(crate) fn is_all_zero(&self) -> bool {
+        self.0.iter().all(|each| each.load(Acquire) == 0)
+    }
+
+    /// Return true if every bit is allocated.
+    ///
+    /// Use `SeqCst` so that it observes any deallocation that happens
+    /// before a `SeqCst` operation of the caller, see `Bucket::on_full`.
     pub(crate) fn is_all_one(&self) -> bool {
-        self.0.iter().all(|each| each.load(Relaxed) == usize::MAX)
+        self.0.iter().all(|each| each.load(SeqCst) == usize::MAX)
     }
 }
 
@@ -125,7 +230,7 @@ mod tests {
             Mutex::new(bitvec.into_boxed_bitslice()),
         ));
 
-        let max_index = (LEN * bits) as usize;
+        let max_index = LEN * bits;
 
         let arc_cloned = arc.clone();
         (0..(LEN * bits)).into_par_iter().for_each(|_| {
@@ -156,6 +261,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_allocate_many() {
+        let bits = usize::BITS as usize;
+        let bitmap = BitMap::<LEN>::new();
+
+        let mut indexes = Vec::new();
+        assert_eq!(
+            bitmap.allocate_many(bits + 3, |i| indexes.push(i)),
+            bits + 3
+        );
+        assert_eq!(indexes.len(), bits + 3);
+
+        // Release some bits in the middle of allocated chunks.
+        for &i in indexes.iter().step_by(2) {
+            unsafe { bitmap.deallocate(i) };
+        }
+
+        let n = LEN * bits - indexes.len() / 2;
+        assert_eq!(bitmap.allocate_many(n + 10, |i| indexes.push(i)), n);
+        assert!(bitmap.is_all_one());
+        assert_eq!(bitmap.allocate_many(1, |_| unreachable!()), 0);
+
+        indexes.sort_unstable();
+        indexes.dedup_by_key(|i| *i);
+        // Every bit is allocated once, except for those deallocated.
+        assert_eq!(indexes.len(), LEN * bits);
+        assert!(indexes.iter().copied().eq(0..LEN * bits));
+    }
+
     #[test]
     fn realworld_test() {
         let bits = usize::BITS as usize;
@@ -173,9 +307,8 @@ mod tests {
 
         (0..(LEN * bits * 2)).into_par_iter().for_each(|_| {
             let index = loop {
-                match arc.0.allocate() {
-                    Some(index) => break index,
-                    None => (),
+                if let Some(index) = arc.0.allocate() {
+                    break index;
                 }
             };
             assert!(unsafe { arc.0.load(index as u32) });
@@ -1,22 +1,88 @@
-use super::{bitmap::BitMap, Arc, OptionExt, SliceExt};
+use super::{
+    bitmap::BitMap,
+    error::{Error, RefCntOverflowError},
+    shared::Shared,
+    Arc, Arena, BucketStats, Key, OptionExt, SliceExt,
+};
+
+use core::{
+    cell::UnsafeCell,
+    hint::spin_loop,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    ptr::{self, addr_of_mut},
+};
+use std::sync::{
+    atomic::{fence, AtomicU32, Ordering},
+    OnceLock,
+};
+
+use triomphe::UniqueArc;
+
+/// Type of [`MAX_REFCNT`] and of the refcount returned by
+/// [`ArenaArc::strong_count`].
+///
+/// It is `u32` whichever width of the counter stored along with every
+/// element is selected by the features.
+pub type RefCnt = u32;
+
+/// Maximum number of references to an element, including the one held
+/// by the arena itself until the element is removed.
+///
+/// It is `127` by default, `32767` if feature `refcnt-u16` is enabled
+/// and `2^31 - 1` if feature `refcnt-u32` is enabled.
+pub const MAX_REFCNT: RefCnt = REFCNT_MASK as RefCnt;
+
+/// Type of the counter stored along with every element.
+///
+/// It is `u8` by default, `u16` if feature `refcnt-u16` is enabled and `u32`
+/// if feature `refcnt-u32` is enabled.
+///
+/// The most significant bit is used to mark the element as removed,
+/// the rest are used to count references.
+mod counter {
+    #[cfg(not(any(feature = "refcnt-u16", feature = "refcnt-u32")))]
+    pub(super) use std::sync::atomic::AtomicU8 as AtomicCounter;
+    #[cfg(not(any(feature = "refcnt-u16", feature = "refcnt-u32")))]
+    pub(super) type Counter = u8;
+
+    #[cfg(all(feature = "refcnt-u16", not(feature = "refcnt-u32")))]
+    pub(super) use std::sync::atomic::AtomicU16 as AtomicCounter;
+    #[cfg(all(feature = "refcnt-u16", not(feature = "refcnt-u32")))]
+    pub(super) type Counter = u16;
+
+    #[cfg(feature = "refcnt-u32")]
+    pub(super) use std::sync::atomic::AtomicU32 as AtomicCounter;
+    #[cfg(feature = "refcnt-u32")]
+    pub(super) type Counter = u32;
+}
+use counter::{AtomicCounter, Counter};
+
+const REMOVED_MASK: Counter = 1 << (Counter::BITS - 1);
+const REFCNT_MASK: Counter = !REMOVED_MASK;
 
-use core::{array, cell::UnsafeCell, hint::spin_loop, ops::Deref};
-use std::sync::atomic::{fence, AtomicU8, Ordering};
+fn increment_refcnt(refcnt: Counter) -> Counter {
+    if (refcnt & REFCNT_MASK) == REFCNT_MASK {
+        panic!("ArenaArc can have at most MAX_REFCNT refcount");
+    }
 
-const REMOVED_MASK: u8 = 1 << (u8::BITS - 1);
-const REFCNT_MASK: u8 = !REMOVED_MASK;
-pub const MAX_REFCNT: u8 = REFCNT_MASK;
+    refcnt + 1
+}
 
 #[derive(Debug)]
 struct Entry<T> {
-    counter: AtomicU8,
+    counter: AtomicCounter,
+    /// Bumped every time the entry is reused, so that a [`Key`] obtained
+    /// for a previous value no longer matches.
+    generation: AtomicU32,
     val: UnsafeCell<Option<T>>,
 }
 
 impl<T> Entry<T> {
-    const fn new() -> Self {
+    const fn new(generation: u32) -> Self {
         Self {
-            counter: AtomicU8::new(0),
+            counter: AtomicCounter::new(0),
+            generation: AtomicU32::new(generation),
             val: UnsafeCell::new(None),
         }
     }
@@ -45,7 +111,16 @@ impl<T> Drop for Entry<T> {
 #[derive(Debug)]
 pub(crate) struct Bucket<T, const BITARRAY_LEN: usize, const LEN: usize> {
     bitset: BitMap<BITARRAY_LEN>,
-    entries: [Entry<T>; LEN],
+    /// Number of elements inserted and not yet removed.
+    live: AtomicU32,
+    /// Number of elements removed but still referenced.
+    zombies: AtomicU32,
+    /// Allocated before the first slot is allocated, so that reserving
+    /// buckets is cheap and only buckets in use take up memory.
+    entries: OnceLock<Box<[Entry<T>]>>,
+    /// Generation of every entry once they are allocated.
+    generation: u32,
+    shared: Arc<Shared>,
 }
 
 unsafe impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Sync
@@ -58,112 +133,319 @@ unsafe impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Send
 {
 }
 
-impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Default
-    for Bucket<T, BITARRAY_LEN, LEN>
-{
-    fn default() -> Self {
-        Self::new()
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Bucket<T, BITARRAY_LEN, LEN> {
+    /// Create the bucket directly in heap memory, so that a large
+    /// `BITARRAY_LEN` does not overflow the stack.
+    pub(crate) fn new(shared: Arc<Shared>) -> Arc<Self> {
+        // Buckets are only dropped by `Arena::shrink_to_fit`, which takes
+        // `&mut Arena`, thus `Relaxed` is enough.
+        let generation = shared.generation_floor.load(Ordering::Relaxed);
+
+        let mut this = UniqueArc::<Self>::new_uninit();
+        let ptr = this.as_mut_ptr().cast::<Self>();
+
+        // Safety: `ptr` is valid for writes and properly aligned.
+        //
+        // All-zero is a valid bit pattern for the bitmap and the other
+        // atomics, meaning that no slot is allocated and there is no element.
+        unsafe {
+            ptr::write_bytes(ptr, 0, 1);
+
+            addr_of_mut!((*ptr).entries).write(OnceLock::new());
+            addr_of_mut!((*ptr).generation).write(generation);
+            addr_of_mut!((*ptr).shared).write(shared);
+        }
+
+        // Safety: every field is initialized above.
+        unsafe { UniqueArc::assume_init(this) }.shareable()
     }
-}
 
-impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Bucket<T, BITARRAY_LEN, LEN> {
-    pub(crate) fn new() -> Self {
-        Self {
-            bitset: BitMap::new(),
-            entries: array::from_fn(|_| Entry::new()),
+    /// Return the entries, allocating them if this is the first call.
+    fn entries_or_init(&self) -> &[Entry<T>] {
+        self.entries
+            .get_or_init(|| (0..LEN).map(|_| Entry::new(self.generation)).collect())
+    }
+
+    /// # Safety
+    ///
+    /// `index` < `LEN` and the entries must be allocated, which is the case
+    /// if the slot is reserved or there is any reference to it.
+    unsafe fn get_entry_unchecked(&self, index: usize) -> &Entry<T> {
+        self.entries
+            .get()
+            .unwrap_unchecked_on_release()
+            .get_unchecked_on_release(index)
+    }
+
+    pub(crate) fn live_count(&self) -> u32 {
+        self.live.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn zombie_count(&self) -> u32 {
+        self.zombies.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn stats(&self) -> BucketStats {
+        BucketStats {
+            live: self.live_count(),
+            zombies: self.zombie_count(),
+            allocated: self.bitset.count_ones(),
         }
     }
 
+    /// The element is removed from the bucket, but is still referenced.
+    fn on_removed(&self) {
+        self.live.fetch_sub(1, Ordering::Relaxed);
+        self.zombies.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg(test)]
     pub(crate) fn try_insert(
         this: &Arc<Self>,
         bucket_index: u32,
         value: T,
     ) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, T> {
-        let index = match this.bitset.allocate() {
+        match Self::try_reserve(this, bucket_index, None) {
+            Some(vacant) => Ok(vacant.insert(value)),
+            None => Err(value),
+        }
+    }
+
+    /// Allocate a slot without putting any value in it.
+    ///
+    /// If `word` is given, the search starts at that `usize` of the bitmap
+    /// instead of a per-thread position.
+    pub(crate) fn try_reserve(
+        this: &Arc<Self>,
+        bucket_index: u32,
+        word: Option<usize>,
+    ) -> Option<VacantSlot<T, BITARRAY_LEN, LEN>> {
+        this.entries_or_init();
+        let index = match word {
+            Some(word) => this.bitset.allocate_from(word),
+            None => this.bitset.allocate(),
+        };
+        let index = match index {
             Some(index) => index,
-            None => return Err(value),
+            None => {
+                this.on_full(bucket_index);
+                return None;
+            }
         };
 
-        // Safety: index <= LEN
-        let entry = unsafe { this.entries.get_unchecked_on_release(index) };
+        // Safety: index <= LEN and is just allocated
+        Some(unsafe { Self::reserve_allocated(this, bucket_index, index) })
+    }
+
+    /// Allocate the slot at `index` without putting any value in it,
+    /// return `None` if it is already allocated.
+    ///
+    /// # Safety
+    ///
+    /// `index` < `LEN`
+    pub(crate) unsafe fn try_reserve_at(
+        this: &Arc<Self>,
+        bucket_index: u32,
+        index: u32,
+    ) -> Option<VacantSlot<T, BITARRAY_LEN, LEN>> {
+        let index = index as usize;
+
+        this.entries_or_init();
+        if !this.bitset.allocate_at(index) {
+            return None;
+        }
+
+        Some(Self::reserve_allocated(this, bucket_index, index))
+    }
+
+    /// Allocate at most `n` slots without putting any value in them,
+    /// return the number of slots allocated.
+    pub(crate) fn try_reserve_many(
+        this: &Arc<Self>,
+        bucket_index: u32,
+        n: usize,
+        vacants: &mut Vec<VacantSlot<T, BITARRAY_LEN, LEN>>,
+    ) -> usize {
+        this.entries_or_init();
+        let allocated = this.bitset.allocate_many(n, |index| {
+            // Safety: index <= LEN and is just allocated
+            vacants.push(unsafe { Self::reserve_allocated(this, bucket_index, index) })
+        });
+
+        if allocated < n {
+            this.on_full(bucket_index);
+        }
+
+        allocated
+    }
+
+    /// Mark the bucket as full in `Shared` after failing to allocate a slot.
+    ///
+    /// The bitmap is checked again after marking it, so that a slot freed
+    /// concurrently is either observed here, or the thread freeing it
+    /// observes the mark and clears it.
+    fn on_full(&self, bucket_index: u32) {
+        let full_buckets = &self.shared.full_buckets;
+
+        full_buckets.mark_full(bucket_index);
+
+        if !self.bitset.is_all_one() && full_buckets.mark_not_full(bucket_index) {
+            // A thread might have skipped this bucket and started waiting
+            // for a slot while it is marked.
+            self.shared.notify_slot_freed();
+        }
+    }
+
+    /// Free the slot at `index` and wake up threads waiting for a slot.
+    ///
+    /// # Safety
+    ///
+    /// `index` < `LEN` and the slot must be allocated.
+    unsafe fn deallocate(&self, bucket_index: u32, index: usize) {
+        self.bitset.deallocate(index);
+        self.shared.full_buckets.mark_not_full(bucket_index);
+        self.shared.notify_slot_freed();
+    }
+
+    /// Return true if no slot is allocated, in which case the generations
+    /// are recorded in `Shared` so that the bucket can be dropped.
+    ///
+    /// It must only be called with `&mut Arena`, so that no slot can be
+    /// allocated concurrently.
+    pub(crate) fn try_retire(&self) -> bool {
+        if self.bitset.is_all_zero() {
+            // The slots are freed using `SeqCst`, so loading the bitmap
+            // with `Acquire` synchronizes with it and the generations
+            // are up to date.
+            let generation = self.entries.get().map_or(0, |entries| {
+                entries
+                    .iter()
+                    .map(|entry| entry.generation.load(Ordering::Relaxed))
+                    .max()
+                    .unwrap_or(0)
+            });
+            self.shared
+                .generation_floor
+                .fetch_max(generation, Ordering::Relaxed);
+
+            true
+        } else {
+            false
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `index` <= `LEN` and must be just allocated in `this.bitset`.
+    unsafe fn reserve_allocated(
+        this: &Arc<Self>,
+        bucket_index: u32,
+        index: usize,
+    ) -> VacantSlot<T, BITARRAY_LEN, LEN> {
+        let entry = this.get_entry_unchecked(index);
 
         // Use `Acquire` here to make sure option is set to None before
         // the entry is reused again.
         let prev_refcnt = entry.counter.load(Ordering::Acquire);
         debug_assert_eq!(prev_refcnt, 0);
 
-        let ptr = entry.val.get();
-        // Safety: ptr can only accessed by this thread
-        let res = unsafe { ptr.replace(Some(value)) };
-        debug_assert!(res.is_none());
+        // Only this thread can access the entry now, the new generation
+        // is published along with the value in `VacantSlot::insert`.
+        let generation = entry.generation.load(Ordering::Relaxed).wrapping_add(1);
+        entry.generation.store(generation, Ordering::Relaxed);
 
-        // 1 for the ArenaArc, another is for the Bucket itself.
-        //
-        // Set counter after option is set to `Some(...)` to avoid
-        // race condition with `remove`.
-        if cfg!(debug_assertions) {
-            let prev_refcnt = entry.counter.swap(2, Ordering::Relaxed);
-            assert_eq!(prev_refcnt, 0);
-        } else {
-            entry.counter.store(2, Ordering::Relaxed);
-        }
+        // Mark it as removed with no reference, so that `access_impl`
+        // treats it as vacant instead of waiting for the value, which
+        // might take arbitrary long.
+        entry.counter.store(REMOVED_MASK, Ordering::Relaxed);
 
         let index = index as u32;
 
-        Ok(ArenaArc {
+        VacantSlot {
             slot: bucket_index * (LEN as u32) + index,
             index,
             bucket: Arc::clone(this),
-        })
+        }
     }
 
+    /// If `wait` is false, return `Error::Initializing` instead of busy
+    /// looping when the slot is not fully initialized.
+    ///
     /// # Safety
     ///
     /// `index` <= `LEN`
     unsafe fn access_impl(
-        this: Arc<Self>,
+        this: &Arc<Self>,
         bucket_index: u32,
         index: u32,
-        update_refcnt: fn(u8) -> u8,
-    ) -> Option<ArenaArc<T, BITARRAY_LEN, LEN>> {
+        update_refcnt: fn(Counter) -> Counter,
+        wait: bool,
+    ) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error> {
+        // Slots cannot be allocated before the entries are.
+        let entries = match this.entries.get() {
+            Some(entries) => entries,
+            None => return Err(Error::Vacant),
+        };
+
         if this.bitset.load(index) {
-            let counter = &this
-                .entries
-                .get_unchecked_on_release(index as usize)
-                .counter;
+            let counter = &entries.get_unchecked_on_release(index as usize).counter;
             let mut refcnt = counter.load(Ordering::Relaxed);
 
             loop {
+                if refcnt == REMOVED_MASK {
+                    // The slot is reserved by a `VacantSlot`, or the last
+                    // `ArenaArc` is dropped and the slot is about to be freed.
+                    return Err(Error::Vacant);
+                }
+
                 if (refcnt & REMOVED_MASK) != 0 {
-                    return None;
+                    return Err(Error::Removed);
                 }
 
                 if refcnt == 0 {
-                    // The variable is not yet fully initialized.
+                    if !wait {
+                        return Err(Error::Initializing);
+                    }
+
+                    // The variable is not yet fully initialized, or the
+                    // last `ArenaArc` is dropped and the slot is about
+                    // to be freed.
                     // Reload the refcnt and check again.
                     spin_loop();
+
+                    if !this.bitset.load(index) {
+                        return Err(Error::Vacant);
+                    }
+
                     refcnt = counter.load(Ordering::Relaxed);
                     continue;
                 }
 
+                let new_refcnt = update_refcnt(refcnt);
+
                 match counter.compare_exchange_weak(
                     refcnt,
-                    update_refcnt(refcnt),
-                    Ordering::Relaxed,
+                    new_refcnt,
+                    Ordering::Acquire,
                     Ordering::Relaxed,
                 ) {
-                    Ok(_) => break,
+                    Ok(_) => {
+                        if (new_refcnt & REMOVED_MASK) != 0 {
+                            this.on_removed();
+                        }
+                        break;
+                    }
                     Err(new_refcnt) => refcnt = new_refcnt,
                 }
             }
 
-            Some(ArenaArc {
+            Ok(ArenaArc {
                 slot: bucket_index * (LEN as u32) + index,
                 index,
-                bucket: this,
+                bucket: Arc::clone(this),
             })
         } else {
-            None
+            Err(Error::Vacant)
         }
     }
 
@@ -171,22 +453,126 @@ impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Bucket<T, BITA
     ///
     /// `index` <= `LEN`
     pub(crate) unsafe fn get(
-        this: Arc<Self>,
+        this: &Arc<Self>,
         bucket_index: u32,
         index: u32,
-    ) -> Option<ArenaArc<T, BITARRAY_LEN, LEN>> {
-        Self::access_impl(this, bucket_index, index, |refcnt| refcnt + 1)
+    ) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error> {
+        Self::access_impl(this, bucket_index, index, increment_refcnt, true)
     }
 
     /// # Safety
     ///
     /// `index` <= `LEN`
     pub(crate) unsafe fn remove(
-        this: Arc<Self>,
+        this: &Arc<Self>,
         bucket_index: u32,
         index: u32,
-    ) -> Option<ArenaArc<T, BITARRAY_LEN, LEN>> {
-        Self::access_impl(this, bucket_index, index, |refcnt| refcnt | REMOVED_MASK)
+    ) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error> {
+        Self::access_impl(
+            this,
+            bucket_index,
+            index,
+            |refcnt| refcnt | REMOVED_MASK,
+            true,
+        )
+    }
+
+    /// # Safety
+    ///
+    /// `index` <= `LEN`
+    pub(crate) unsafe fn try_get(
+        this: &Arc<Self>,
+        bucket_index: u32,
+        index: u32,
+    ) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error> {
+        Self::access_impl(this, bucket_index, index, increment_refcnt, false)
+    }
+
+    /// # Safety
+    ///
+    /// `index` <= `LEN`
+    pub(crate) unsafe fn try_remove(
+        this: &Arc<Self>,
+        bucket_index: u32,
+        index: u32,
+    ) -> Result<ArenaArc<T, BITARRAY_LEN, LEN>, Error> {
+        Self::access_impl(
+            this,
+            bucket_index,
+            index,
+            |refcnt| refcnt | REMOVED_MASK,
+            false,
+        )
+    }
+}
+
+/// Iterator over the elements of one bucket.
+///
+/// The bitmap is loaded one `usize` at a time, so elements inserted
+/// concurrently may or may not be yielded.
+#[derive(Debug)]
+pub(crate) struct Iter<T, const BITARRAY_LEN: usize, const LEN: usize> {
+    bucket: Arc<Bucket<T, BITARRAY_LEN, LEN>>,
+    bucket_index: u32,
+    /// Index of the next `usize` in the bitmap to load.
+    chunk_index: usize,
+    /// Bits of the current `usize` that are not yet visited.
+    chunk: usize,
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Iter<T, BITARRAY_LEN, LEN> {
+    pub(crate) fn new(bucket: Arc<Bucket<T, BITARRAY_LEN, LEN>>, bucket_index: u32) -> Self {
+        Self {
+            bucket,
+            bucket_index,
+            chunk_index: 0,
+            chunk: 0,
+        }
+    }
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Iterator
+    for Iter<T, BITARRAY_LEN, LEN>
+{
+    type Item = ArenaArc<T, BITARRAY_LEN, LEN>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bits = usize::BITS;
+
+        loop {
+            while self.chunk == 0 {
+                if self.chunk_index == BITARRAY_LEN {
+                    return None;
+                }
+
+                // Safety: `self.chunk_index` < `BITARRAY_LEN`
+                self.chunk = unsafe { self.bucket.bitset.load_chunk(self.chunk_index) };
+                self.chunk_index += 1;
+            }
+
+            let bit = self.chunk.trailing_zeros();
+            self.chunk &= self.chunk - 1;
+
+            let index = (self.chunk_index as u32 - 1) * bits + bit;
+
+            // Slots that are being initialized or are already removed are
+            // skipped instead of waited on.
+            //
+            // Safety: `index` < `LEN`
+            let arc = unsafe {
+                Bucket::access_impl(
+                    &self.bucket,
+                    self.bucket_index,
+                    index,
+                    increment_refcnt,
+                    false,
+                )
+            };
+
+            if let Ok(arc) = arc {
+                break Some(arc);
+            }
+        }
     }
 }
 
@@ -208,26 +594,42 @@ impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> ArenaArc<T, BI
         this.slot
     }
 
+    /// Return the [`Key`] of this element, which can be used with
+    /// [`Arena::get_by_key`](crate::Arena::get_by_key) and
+    /// [`Arena::remove_by_key`](crate::Arena::remove_by_key) to detect
+    /// that the slot has been reused for another value.
+    ///
+    /// This is how the key of an element returned by
+    /// [`Arena::insert`](crate::Arena::insert) is obtained.
+    pub fn key(this: &Self) -> Key {
+        Key {
+            slot: this.slot,
+            generation: Self::get_entry(this).generation.load(Ordering::Relaxed),
+        }
+    }
+
     fn get_index(this: &Self) -> usize {
         this.index as usize
     }
 
     fn get_entry(this: &Self) -> &Entry<T> {
-        // Safety: `Self::get_index(this)` <= `LEN`
-        let entry = unsafe {
-            this.bucket
-                .entries
-                .get_unchecked_on_release(Self::get_index(this))
-        };
+        let entry = Self::get_entry_unchecked(this);
         debug_assert!((entry.counter.load(Ordering::Relaxed) & REFCNT_MASK) > 0);
         entry
     }
 
-    pub fn strong_count(this: &Self) -> u8 {
+    /// Same as `get_entry`, except that the refcount is not checked.
+    fn get_entry_unchecked(this: &Self) -> &Entry<T> {
+        // Safety: `Self::get_index(this)` <= `LEN` and `this` is
+        // a reference to it.
+        unsafe { this.bucket.get_entry_unchecked(Self::get_index(this)) }
+    }
+
+    pub fn strong_count(this: &Self) -> RefCnt {
         let entry = Self::get_entry(this);
         let cnt = entry.counter.load(Ordering::Relaxed) & REFCNT_MASK;
         debug_assert!(cnt > 0);
-        cnt
+        cnt as RefCnt
     }
 
     pub fn is_removed(this: &Self) -> bool {
@@ -264,11 +666,84 @@ impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> ArenaArc<T, BI
                 Ordering::Relaxed,
                 Ordering::Relaxed,
             ) {
-                Ok(_) => return true,
+                Ok(_) => {
+                    this.bucket.on_removed();
+                    return true;
+                }
                 Err(new_refcnt) => refcnt = new_refcnt,
             }
         }
     }
+
+    /// Create an [`ArenaWeak`] to this element, which does not keep
+    /// the value alive.
+    pub fn downgrade(this: &Self) -> ArenaWeak<T, BITARRAY_LEN, LEN> {
+        ArenaWeak {
+            key: Self::key(this),
+            index: this.index,
+            bucket: Arc::clone(&this.bucket),
+        }
+    }
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> ArenaArc<T, BITARRAY_LEN, LEN> {
+    /// Return a mutable reference to the value if this is the only
+    /// reference to a removed element.
+    ///
+    /// Elements that are not removed can always be accessed through
+    /// the `Arena`, so `None` is returned for them.
+    pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+        let entry = Self::get_entry(this);
+
+        // Use `Acquire` so that all accesses to the value through other
+        // references happen before it is mutated here.
+        //
+        // Once removed, no new reference can be created from the `Arena` or
+        // an `ArenaWeak`, and `this` is borrowed mutably so it cannot be
+        // cloned either.
+        if entry.counter.load(Ordering::Acquire) == (REMOVED_MASK | 1) {
+            // Safety: this is the only reference to the value.
+            Some(unsafe { (*entry.val.get()).as_mut().unwrap_unchecked_on_release() })
+        } else {
+            None
+        }
+    }
+
+    /// Same as [`ArenaArc::get_mut`], except that if it would return `None`,
+    /// then the value is cloned into a new slot of `arena` first, which is
+    /// removed right away and replaces `this`.
+    ///
+    /// The element `this` pointed to is left as is, and the new one stays
+    /// a removed element of `arena` until the last reference to it is
+    /// dropped.
+    ///
+    /// Return `None` without cloning if the value has to be cloned but
+    /// `arena` already has `arena.limit()` buckets and all of them are full,
+    /// instead of blocking as [`Arena::insert`] does.
+    ///
+    /// # Panics
+    ///
+    /// If `this` does not belong to `arena`.
+    pub fn make_mut<'a>(
+        this: &'a mut Self,
+        arena: &Arena<T, BITARRAY_LEN, LEN>,
+    ) -> Option<&'a mut T>
+    where
+        T: Clone,
+    {
+        assert!(
+            Arc::ptr_eq(&arena.shared, &this.bucket.shared),
+            "ArenaArc::make_mut is called with another Arena"
+        );
+
+        if Self::get_mut(this).is_none() {
+            let arc = arena.reserve_slot_or_grow()?.insert(T::clone(this));
+            Self::remove(&arc);
+            *this = arc;
+        }
+
+        Self::get_mut(this)
+    }
 }
 
 impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Deref
@@ -295,8 +770,8 @@ impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Clone
         // reference alive.
         //
         // [1]: https://www.boost.org/doc/libs/1_77_0/doc/html/atomic/usage_examples.html
-        if (entry.counter.fetch_add(1, Ordering::Relaxed) & REFCNT_MASK) == MAX_REFCNT {
-            panic!("ArenaArc can have at most u8::MAX refcount");
+        if (entry.counter.fetch_add(1, Ordering::Relaxed) & REFCNT_MASK) == REFCNT_MASK {
+            panic!("ArenaArc can have at most MAX_REFCNT refcount");
         }
 
         Self {
@@ -307,11 +782,88 @@ impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Clone
     }
 }
 
-impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Drop
-    for ArenaArc<T, BITARRAY_LEN, LEN>
-{
-    fn drop(&mut self) {
-        let entry = Self::get_entry(self);
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> ArenaArc<T, BITARRAY_LEN, LEN> {
+    /// Same as [`Clone::clone`], except that it returns an error instead of
+    /// panicking if there are already `MAX_REFCNT` references.
+    pub fn try_clone(this: &Self) -> Result<Self, RefCntOverflowError> {
+        let counter = &Self::get_entry(this).counter;
+        let mut refcnt = counter.load(Ordering::Relaxed);
+
+        loop {
+            if (refcnt & REFCNT_MASK) == REFCNT_MASK {
+                break Err(RefCntOverflowError);
+            }
+
+            // Relaxed is enough for the same reason as `Clone::clone`.
+            match counter.compare_exchange_weak(
+                refcnt,
+                refcnt + 1,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    break Ok(Self {
+                        slot: this.slot,
+                        index: this.index,
+                        bucket: Arc::clone(&this.bucket),
+                    })
+                }
+                Err(new_refcnt) => refcnt = new_refcnt,
+            }
+        }
+    }
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> ArenaArc<T, BITARRAY_LEN, LEN> {
+    /// Return the value if this is the last reference to a removed element,
+    /// otherwise return `this` back.
+    ///
+    /// The slot is freed on success.
+    pub fn try_unwrap(this: Self) -> Result<T, Self> {
+        let entry = Self::get_entry(&this);
+
+        // Use `Acquire` for the same reason as the fence in `drop`.
+        //
+        // Setting counter to `REMOVED_MASK` makes `access_impl` treat
+        // the slot as vacant while the value is moved out.
+        if entry
+            .counter
+            .compare_exchange(
+                REMOVED_MASK | 1,
+                REMOVED_MASK,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return Err(this);
+        }
+
+        // Safety: `entry.val` can only be accessed by this thread now
+        // and it is `Some(...)` until the last reference is dropped.
+        let value = unsafe { (*entry.val.get()).take().unwrap_unchecked_on_release() };
+
+        Self::free_slot(&this);
+        Self::forget(this);
+
+        Ok(value)
+    }
+
+    /// Drop this reference and return the value if it is the last
+    /// reference to a removed element.
+    ///
+    /// Unlike [`ArenaArc::try_unwrap`], if it is called on every reference
+    /// to a removed element, then exactly one of them returns the value.
+    pub fn into_inner(this: Self) -> Option<T> {
+        let value = Self::release(&this);
+        Self::forget(this);
+        value
+    }
+
+    /// Decrement the refcount, return the value and free the slot if this
+    /// is the last reference.
+    fn release(this: &Self) -> Option<T> {
+        let entry = Self::get_entry(this);
 
         // According to [Boost documentation][1], decreasing refcount must be done
         // using Release to ensure the write to the value happens before the
@@ -319,34 +871,312 @@ impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Drop
         //
         // [1]: https://www.boost.org/doc/libs/1_77_0/doc/html/atomic/usage_examples.html
         let prev_counter = entry.counter.fetch_sub(1, Ordering::Release);
-        let prev_refcnt = prev_counter & MAX_REFCNT;
+        let prev_refcnt = prev_counter & REFCNT_MASK;
 
         debug_assert_ne!(prev_refcnt, 0);
 
-        if prev_refcnt == 1 {
-            debug_assert_eq!(prev_counter, REMOVED_MASK | 1);
+        if prev_refcnt != 1 {
+            if prev_counter == (REMOVED_MASK | 2) {
+                // Only one reference to the removed element is left, wake up
+                // `Arena::take_wait` that might be waiting for it.
+                //
+                // Pairs with the fence in `Shared::wait_for_slot`.
+                fence(Ordering::SeqCst);
+                this.bucket.shared.notify_slot_freed();
+            }
 
-            // This is the last reference, drop the value.
+            return None;
+        }
 
-            // According to [Boost documentation][1], an Acquire fence must be used
-            // before dropping value to ensure that all write to the value happens
-            // before it is dropped.
-            fence(Ordering::Acquire);
+        debug_assert_eq!(prev_counter, REMOVED_MASK | 1);
 
-            // Now entry.counter == 0
+        // This is the last reference, take the value.
 
-            // Safety: `entry.val` can only be accessed by this thread now.
-            let option = unsafe { &mut *entry.val.get() };
-            *option = None;
+        // According to [Boost documentation][1], an Acquire fence must be used
+        // before dropping value to ensure that all write to the value happens
+        // before it is dropped.
+        fence(Ordering::Acquire);
 
-            // Make sure drop is written to memory before
-            // the entry is reused again.
-            entry.counter.store(0, Ordering::Release);
+        // Now entry.counter == REMOVED_MASK
 
-            // Safety:
-            //
-            // `Self::get_index(self)` <= `LEN` == `BITARRAY_LEN / usize::BITS`
-            unsafe { self.bucket.bitset.deallocate(Self::get_index(self)) };
+        // Safety: `entry.val` can only be accessed by this thread now.
+        let value = unsafe { (*entry.val.get()).take() };
+
+        Self::free_slot(this);
+
+        value
+    }
+
+    /// Free the slot, `entry.val` must already be taken.
+    fn free_slot(this: &Self) {
+        let entry = Self::get_entry_unchecked(this);
+
+        this.bucket.zombies.fetch_sub(1, Ordering::Relaxed);
+
+        // Make sure the value is taken before the entry is reused again.
+        entry.counter.store(0, Ordering::Release);
+
+        // Safety:
+        //
+        // `Self::get_index(this)` <= `LEN` == `BITARRAY_LEN / usize::BITS`
+        unsafe {
+            this.bucket
+                .deallocate(this.slot / (LEN as u32), Self::get_index(this))
+        };
+    }
+
+    /// Drop `this` without running `ArenaArc::drop`.
+    fn forget(this: Self) {
+        let this = ManuallyDrop::new(this);
+
+        // Safety: `this` is never dropped, so the `Arc` is moved
+        // out of it exactly once.
+        drop(unsafe { ptr::read(&this.bucket) });
+    }
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Drop
+    for ArenaArc<T, BITARRAY_LEN, LEN>
+{
+    fn drop(&mut self) {
+        drop(Self::release(self));
+    }
+}
+
+/// A slot reserved by [`Arena::reserve_slot`](crate::Arena::reserve_slot),
+/// which is vacant until a value is inserted into it.
+///
+/// The slot is freed if it is dropped without [`VacantSlot::insert`]
+/// being called.
+#[derive(Debug)]
+pub struct VacantSlot<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> {
+    slot: u32,
+    index: u32,
+    bucket: Arc<Bucket<T, BITARRAY_LEN, LEN>>,
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> VacantSlot<T, BITARRAY_LEN, LEN> {
+    /// Return the slot the value will be inserted into.
+    pub fn slot(&self) -> u32 {
+        self.slot
+    }
+
+    /// Return the [`Key`] the value will have once inserted.
+    pub fn key(&self) -> Key {
+        Key {
+            slot: self.slot,
+            generation: self.get_entry().generation.load(Ordering::Relaxed),
+        }
+    }
+
+    fn get_entry(&self) -> &Entry<T> {
+        // Safety: `self.index` < `LEN` and it is reserved.
+        unsafe { self.bucket.get_entry_unchecked(self.index as usize) }
+    }
+
+    /// Put `value` into the slot and make it visible to other threads.
+    pub fn insert(self, value: T) -> ArenaArc<T, BITARRAY_LEN, LEN> {
+        ArenaBox::into_shared(self.insert_unique(value))
+    }
+
+    /// Put `value` into the slot without making it visible to other threads.
+    pub(crate) fn insert_unique(self, value: T) -> ArenaBox<T, BITARRAY_LEN, LEN> {
+        let this = ManuallyDrop::new(self);
+        let entry = this.get_entry();
+
+        let ptr = entry.val.get();
+        // Safety: ptr can only accessed by this thread
+        let res = unsafe { ptr.replace(Some(value)) };
+        debug_assert!(res.is_none());
+
+        ArenaBox {
+            slot: this.slot,
+            index: this.index,
+            // Safety: `this` is never dropped, so the `Arc` is moved
+            // out of it exactly once.
+            bucket: unsafe { ptr::read(&this.bucket) },
+        }
+    }
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Drop
+    for VacantSlot<T, BITARRAY_LEN, LEN>
+{
+    fn drop(&mut self) {
+        let entry = self.get_entry();
+
+        // Make sure the counter is reset before the slot is reused.
+        entry.counter.store(0, Ordering::Release);
+
+        // Safety: `self.index` < `LEN`
+        unsafe {
+            self.bucket
+                .deallocate(self.slot / (LEN as u32), self.index as usize)
+        };
+    }
+}
+
+/// Unique owner of an element created by
+/// [`Arena::insert_unique`](crate::Arena::insert_unique).
+///
+/// The element is not visible to other threads until
+/// [`ArenaBox::into_shared`] is called: [`Arena::get`](crate::Arena::get)
+/// on its slot returns `Error::Vacant`.
+///
+/// The slot is freed if it is dropped without being shared.
+#[derive(Debug)]
+pub struct ArenaBox<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> {
+    slot: u32,
+    index: u32,
+    bucket: Arc<Bucket<T, BITARRAY_LEN, LEN>>,
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> ArenaBox<T, BITARRAY_LEN, LEN> {
+    pub fn slot(this: &Self) -> u32 {
+        this.slot
+    }
+
+    /// Return the [`Key`] the element will have once shared.
+    pub fn key(this: &Self) -> Key {
+        Key {
+            slot: this.slot,
+            generation: Self::get_entry(this).generation.load(Ordering::Relaxed),
+        }
+    }
+
+    fn get_entry(this: &Self) -> &Entry<T> {
+        // Safety: `this.index` < `LEN` and it is reserved.
+        unsafe { this.bucket.get_entry_unchecked(this.index as usize) }
+    }
+
+    /// Make the element visible to other threads, as if it is inserted
+    /// by [`Arena::insert`](crate::Arena::insert).
+    pub fn into_shared(this: Self) -> ArenaArc<T, BITARRAY_LEN, LEN> {
+        let this = ManuallyDrop::new(this);
+        let entry = Self::get_entry(&this);
+
+        // Count it before it is published, so that removing it never
+        // underflows the counter.
+        this.bucket.live.fetch_add(1, Ordering::Relaxed);
+
+        // 1 for the ArenaArc, another is for the Bucket itself.
+        //
+        // Set counter after option is set to `Some(...)` to avoid
+        // race condition with `remove`.
+        //
+        // Use `Release` here so that `access_impl` observes the value
+        // and the new generation once it acquires a reference.
+        if cfg!(debug_assertions) {
+            let prev_refcnt = entry.counter.swap(2, Ordering::Release);
+            assert_eq!(prev_refcnt, REMOVED_MASK);
+        } else {
+            entry.counter.store(2, Ordering::Release);
+        }
+
+        ArenaArc {
+            slot: this.slot,
+            index: this.index,
+            // Safety: `this` is never dropped, so the `Arc` is moved
+            // out of it exactly once.
+            bucket: unsafe { ptr::read(&this.bucket) },
+        }
+    }
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Deref
+    for ArenaBox<T, BITARRAY_LEN, LEN>
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        let ptr = Self::get_entry(self).val.get();
+
+        // Safety: the value is only accessible through `self`.
+        unsafe { (*ptr).as_ref().unwrap_unchecked_on_release() }
+    }
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> DerefMut
+    for ArenaBox<T, BITARRAY_LEN, LEN>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        let ptr = Self::get_entry(self).val.get();
+
+        // Safety: the value is only accessible through `self`.
+        unsafe { (*ptr).as_mut().unwrap_unchecked_on_release() }
+    }
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Drop
+    for ArenaBox<T, BITARRAY_LEN, LEN>
+{
+    fn drop(&mut self) {
+        let entry = Self::get_entry(self);
+
+        // Safety: the value is only accessible through `self`.
+        let value = unsafe { (*entry.val.get()).take() };
+        debug_assert!(value.is_some());
+
+        // Make sure the value is taken before the entry is reused again.
+        entry.counter.store(0, Ordering::Release);
+
+        // Safety: `self.index` < `LEN`
+        unsafe {
+            self.bucket
+                .deallocate(self.slot / (LEN as u32), self.index as usize)
+        };
+
+        drop(value);
+    }
+}
+
+/// Weak reference to an element created by [`ArenaArc::downgrade`].
+///
+/// It does not keep the value alive, though it does keep the
+/// bucket containing it allocated.
+#[derive(Debug)]
+pub struct ArenaWeak<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> {
+    key: Key,
+    index: u32,
+    bucket: Arc<Bucket<T, BITARRAY_LEN, LEN>>,
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> ArenaWeak<T, BITARRAY_LEN, LEN> {
+    pub fn key(this: &Self) -> Key {
+        this.key
+    }
+
+    /// Return `None` if the value has been removed from the `Arena`,
+    /// dropped, or if the slot is reused for another value.
+    ///
+    /// This function is lock free.
+    pub fn upgrade(this: &Self) -> Option<ArenaArc<T, BITARRAY_LEN, LEN>> {
+        // If the slot is being initialized, then the value must
+        // have been dropped, so there is no need to wait.
+        //
+        // Safety: `this.index` < `LEN`
+        unsafe {
+            Bucket::access_impl(
+                &this.bucket,
+                this.key.slot / (LEN as u32),
+                this.index,
+                increment_refcnt,
+                false,
+            )
+        }
+        .ok()
+        .filter(|arc| ArenaArc::key(arc) == this.key)
+    }
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Clone
+    for ArenaWeak<T, BITARRAY_LEN, LEN>
+{
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key,
+            index: self.index,
+            bucket: Arc::clone(&self.bucket),
         }
     }
 }
@@ -355,6 +1185,7 @@ impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Drop
 mod tests {
     use super::Arc;
     use super::ArenaArc;
+    use super::ArenaWeak;
 
     use parking_lot::Mutex;
     use parking_lot::MutexGuard;
@@ -370,7 +1201,7 @@ mod tests {
 
     #[test]
     fn test_basic() {
-        let bucket: Arc<Bucket<u32>> = Arc::new(Bucket::new());
+        let bucket: Arc<Bucket<u32>> = Bucket::new(Arc::default());
 
         let arcs: Vec<_> = (0..LEN)
             .into_par_iter()
@@ -394,7 +1225,7 @@ mod tests {
             .into_par_iter()
             .enumerate()
             .map(|(i, orig_arc)| {
-                let arc = unsafe { Bucket::get(Arc::clone(&bucket), 0, orig_arc.index) }.unwrap();
+                let arc = unsafe { Bucket::get(&bucket, 0, orig_arc.index) }.unwrap();
 
                 assert_eq!(ArenaArc::strong_count(&arc), 3);
                 assert_eq!(*arc as usize, i);
@@ -410,7 +1241,7 @@ mod tests {
 
     #[test]
     fn test_clone() {
-        let bucket: Arc<Bucket<u32>> = Arc::new(Bucket::new());
+        let bucket: Arc<Bucket<u32>> = Bucket::new(Arc::default());
 
         let arcs: Vec<_> = (0..LEN)
             .into_par_iter()
@@ -447,7 +1278,7 @@ mod tests {
 
     #[test]
     fn test_reuse() {
-        let bucket: Arc<Bucket<u32>> = Arc::new(Bucket::new());
+        let bucket: Arc<Bucket<u32>> = Bucket::new(Arc::default());
 
         let mut arcs: Vec<_> = (0..LEN)
             .into_par_iter()
@@ -463,7 +1294,7 @@ mod tests {
 
         for arc in arcs.drain(arcs.len() / 2..) {
             assert_eq!(ArenaArc::strong_count(&arc), 2);
-            let new_arc = unsafe { Bucket::remove(bucket.clone(), 0, arc.index) }.unwrap();
+            let new_arc = unsafe { Bucket::remove(&bucket, 0, arc.index) }.unwrap();
             assert_eq!(ArenaArc::strong_count(&arc), 2);
 
             assert!(ArenaArc::is_removed(&new_arc));
@@ -505,7 +1336,7 @@ mod tests {
 
     #[test]
     fn test_reuse2() {
-        let bucket: Arc<Bucket<u32>> = Arc::new(Bucket::new());
+        let bucket: Arc<Bucket<u32>> = Bucket::new(Arc::default());
 
         let mut arcs: Vec<_> = (0..LEN)
             .into_par_iter()
@@ -559,7 +1390,7 @@ mod tests {
 
     #[test]
     fn test_concurrent_remove() {
-        let bucket: Arc<Bucket<u32>> = Arc::new(Bucket::new());
+        let bucket: Arc<Bucket<u32>> = Bucket::new(Arc::default());
 
         let arcs: Vec<_> = (0..LEN)
             .into_par_iter()
@@ -575,7 +1406,7 @@ mod tests {
 
         arcs.into_par_iter().for_each(|arc| {
             assert_eq!(ArenaArc::strong_count(&arc), 2);
-            let new_arc = unsafe { Bucket::remove(bucket.clone(), 0, arc.index) }.unwrap();
+            let new_arc = unsafe { Bucket::remove(&bucket, 0, arc.index) }.unwrap();
             assert!(ArenaArc::is_removed(&new_arc));
             assert_eq!(ArenaArc::strong_count(&arc), 2);
 
@@ -586,7 +1417,7 @@ mod tests {
 
     #[test]
     fn test_concurrent_remove2() {
-        let bucket: Arc<Bucket<u32>> = Arc::new(Bucket::new());
+        let bucket: Arc<Bucket<u32>> = Bucket::new(Arc::default());
 
         let arcs: Vec<_> = (0..LEN)
             .into_par_iter()
@@ -608,9 +1439,167 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_try_get_initializing() {
+        use crate::Error;
+
+        let bucket: Arc<Bucket<u32>> = Bucket::new(Arc::default());
+
+        // Simulate an insertion in progress.
+        bucket.entries_or_init();
+        let index = bucket.bitset.allocate().unwrap() as u32;
+
+        unsafe {
+            assert_eq!(
+                Bucket::try_get(&bucket, 0, index).unwrap_err(),
+                Error::Initializing
+            );
+            assert_eq!(
+                Bucket::try_remove(&bucket, 0, index).unwrap_err(),
+                Error::Initializing
+            );
+
+            bucket.bitset.deallocate(index as usize);
+
+            assert_eq!(
+                Bucket::try_get(&bucket, 0, index).unwrap_err(),
+                Error::Vacant
+            );
+            assert_eq!(Bucket::get(&bucket, 0, index).unwrap_err(), Error::Vacant);
+        }
+    }
+
+    /// Cloning up to `u32::MAX / 2` references takes too long.
+    #[cfg(not(feature = "refcnt-u32"))]
+    #[test]
+    fn test_try_clone() {
+        use super::MAX_REFCNT;
+
+        let bucket: Arc<Bucket<u32>> = Bucket::new(Arc::default());
+
+        let arc = Bucket::try_insert(&bucket, 0, 1).unwrap();
+
+        // One reference is held by the bucket itself.
+        let arcs: Vec<_> = (3..MAX_REFCNT)
+            .map(|_| ArenaArc::try_clone(&arc).unwrap())
+            .collect();
+        assert_eq!(ArenaArc::strong_count(&arc), MAX_REFCNT - 1);
+
+        let last = ArenaArc::try_clone(&arc).unwrap();
+        assert_eq!(ArenaArc::strong_count(&arc), MAX_REFCNT);
+
+        assert!(ArenaArc::try_clone(&arc).is_err());
+        assert!(ArenaArc::try_clone(&last).is_err());
+        assert_eq!(ArenaArc::strong_count(&arc), MAX_REFCNT);
+        assert!(!ArenaArc::is_removed(&arc));
+
+        drop(last);
+        drop(arcs);
+        assert_eq!(ArenaArc::strong_count(&arc), 2);
+        assert_eq!(*ArenaArc::try_clone(&arc).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_weak() {
+        let bucket: Arc<Bucket<u32>> = Bucket::new(Arc::default());
+
+        let arc = Bucket::try_insert(&bucket, 0, 1).unwrap();
+        let weak = ArenaArc::downgrade(&arc);
+        assert_eq!(ArenaWeak::key(&weak), ArenaArc::key(&arc));
+
+        let upgraded = ArenaWeak::upgrade(&weak.clone()).unwrap();
+        assert_eq!(*upgraded, 1);
+        assert_eq!(ArenaArc::strong_count(&arc), 3);
+        drop(upgraded);
+        assert_eq!(ArenaArc::strong_count(&arc), 2);
+
+        // No new reference to a removed element, even if `arc` keeps it alive.
+        ArenaArc::remove(&arc);
+        assert!(ArenaWeak::upgrade(&weak).is_none());
+        assert_eq!(ArenaArc::strong_count(&arc), 1);
+        drop(arc);
+        assert!(ArenaWeak::upgrade(&weak).is_none());
+
+        // Reuse every slot, including the one `weak` points to.
+        let arcs: Vec<_> = (0..LEN)
+            .map(|i| Bucket::try_insert(&bucket, 0, i).unwrap())
+            .collect();
+        assert!(arcs
+            .iter()
+            .any(|arc| ArenaArc::slot(arc) == ArenaWeak::key(&weak).slot));
+        assert!(ArenaWeak::upgrade(&weak).is_none());
+    }
+
+    #[test]
+    fn test_try_unwrap() {
+        let bucket: Arc<Bucket<String>> = Bucket::new(Arc::default());
+
+        let arc = Bucket::try_insert(&bucket, 0, "a".to_string()).unwrap();
+        let slot = ArenaArc::slot(&arc);
+
+        // Not removed yet, so the bucket still holds a reference.
+        let arc = ArenaArc::try_unwrap(arc).unwrap_err();
+        assert!(ArenaArc::remove(&arc));
+
+        let cloned = arc.clone();
+        let arc = ArenaArc::try_unwrap(arc).unwrap_err();
+        assert_eq!(ArenaArc::into_inner(cloned), None);
+        assert_eq!(ArenaArc::try_unwrap(arc).unwrap(), "a");
+        assert!(!unsafe { bucket.bitset.load(slot) });
+
+        let arc = Bucket::try_insert(&bucket, 0, "b".to_string()).unwrap();
+        let slot = ArenaArc::slot(&arc);
+        ArenaArc::remove(&arc);
+        assert_eq!(ArenaArc::into_inner(arc).unwrap(), "b");
+        assert!(!unsafe { bucket.bitset.load(slot) });
+    }
+
+    #[test]
+    fn test_lazy_entries() {
+        use crate::Error;
+
+        let bucket: Arc<Bucket<u32>> = Bucket::new(Arc::default());
+        assert!(bucket.entries.get().is_none());
+
+        assert_eq!(
+            unsafe { Bucket::get(&bucket, 0, 0) }.unwrap_err(),
+            Error::Vacant
+        );
+        assert_eq!(super::Iter::new(Arc::clone(&bucket), 0).count(), 0);
+        assert!(bucket.try_retire());
+        assert!(bucket.entries.get().is_none());
+
+        let bucket: Arc<Bucket<u32>> = Bucket::new(Arc::default());
+        let arc = Bucket::try_insert(&bucket, 0, 1).unwrap();
+        assert!(bucket.entries.get().is_some());
+        assert_eq!(
+            *unsafe { Bucket::get(&bucket, 0, ArenaArc::slot(&arc)) }.unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_large_bucket() {
+        const LEN: usize = 1 << 20;
+
+        // The bitmap alone is far larger than the stack of the thread.
+        std::thread::Builder::new()
+            .stack_size(32 * 1024)
+            .spawn(|| {
+                let bucket: Arc<super::Bucket<u8, { LEN / usize::BITS as usize }, LEN>> =
+                    super::Bucket::new(Arc::default());
+
+                let arc = super::Bucket::try_insert(&bucket, 0, 1).unwrap();
+                assert_eq!(*arc, 1);
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
     #[test]
     fn realworld_test() {
-        let bucket: Arc<Bucket<Mutex<u32>>> = Arc::new(Bucket::new());
+        let bucket: Arc<Bucket<Mutex<u32>>> = Bucket::new(Arc::default());
 
         (0..LEN).into_par_iter().for_each(|i| {
             let arc = Bucket::try_insert(&bucket, 0, Mutex::new(i)).unwrap();
@@ -0,0 +1,97 @@
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Number of `Arena`s every thread remembers a cursor for.
+const CURSORS: usize = 8;
+
+/// Bucket and the `usize` in its bitmap that a thread last reserved
+/// a slot in.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Cursor {
+    pub(crate) bucket: u32,
+    pub(crate) word: u32,
+}
+
+impl Cursor {
+    pub(crate) fn new(slot: u32, len: u32) -> Self {
+        Self {
+            bucket: slot / len,
+            word: (slot % len) / usize::BITS,
+        }
+    }
+}
+
+thread_local! {
+    /// Cursors of the current thread, indexed by `ArenaId` modulo
+    /// `CURSORS` and tagged with the id.
+    static THREAD_CURSORS: [Cell<(usize, Cursor)>; CURSORS] =
+        const { [const { Cell::new((0, Cursor { bucket: 0, word: 0 })) }; CURSORS] };
+}
+
+/// Unique id of an `Arena`, used to find its cursor in the current thread.
+///
+/// `0` is never used, so that it never matches an empty cursor.
+#[derive(Debug)]
+pub(crate) struct ArenaId(usize);
+
+impl Default for ArenaId {
+    fn default() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl ArenaId {
+    /// Return the cursor stored by the current thread, if it is not evicted
+    /// by another `Arena` since then.
+    ///
+    /// The cursor is only a hint and might be out of range.
+    pub(crate) fn load_cursor(&self) -> Option<Cursor> {
+        THREAD_CURSORS
+            .try_with(|cursors| {
+                let (id, cursor) = cursors[self.0 % CURSORS].get();
+                (id == self.0).then_some(cursor)
+            })
+            .ok()
+            .flatten()
+    }
+
+    pub(crate) fn store_cursor(&self, cursor: Cursor) {
+        // The thread local might already be destroyed if it is called
+        // while the thread is exiting, in which case there is no need to
+        // remember anything.
+        let _ = THREAD_CURSORS.try_with(|cursors| cursors[self.0 % CURSORS].set((self.0, cursor)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArenaId, Cursor, CURSORS};
+
+    #[test]
+    fn test() {
+        let ids: Vec<ArenaId> = (0..=CURSORS).map(|_| ArenaId::default()).collect();
+        let (first, last) = (&ids[0], &ids[CURSORS]);
+
+        assert_eq!(first.load_cursor(), None);
+
+        let cursor = Cursor::new(usize::BITS * 3 + 1, usize::BITS * 2);
+        assert_eq!(cursor, Cursor { bucket: 1, word: 1 });
+
+        first.store_cursor(cursor);
+        assert_eq!(first.load_cursor(), Some(cursor));
+        assert_eq!(last.load_cursor(), None);
+
+        // Both ids are mapped to the same cursor.
+        last.store_cursor(Cursor::default());
+        assert_eq!(first.load_cursor(), None);
+        assert_eq!(last.load_cursor(), Some(Cursor::default()));
+
+        std::thread::spawn(move || assert_eq!(ids[CURSORS].load_cursor(), None))
+            .join()
+            .unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+use core::fmt;
+use std::error::Error as StdError;
+
+/// Returned by [`Arena::get`](crate::Arena::get),
+/// [`Arena::remove`](crate::Arena::remove) and their variants.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Error {
+    /// The slot is not in any of the buckets allocated.
+    OutOfRange,
+    /// There is no value stored in the slot.
+    Vacant,
+    /// The value in the slot is already removed.
+    Removed,
+    /// A value is being inserted into the slot, returned by
+    /// [`Arena::try_get`](crate::Arena::try_get) and
+    /// [`Arena::try_remove`](crate::Arena::try_remove) instead of
+    /// waiting for the insertion to finish.
+    Initializing,
+    /// The slot has been reused for another value since the [`Key`](crate::Key)
+    /// was obtained.
+    Reused,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::OutOfRange => "slot is out of range",
+            Error::Vacant => "slot is vacant",
+            Error::Removed => "value is already removed",
+            Error::Initializing => "value is being inserted",
+            Error::Reused => "slot is reused for another value",
+        })
+    }
+}
+
+impl StdError for Error {}
+
+/// Returned by [`ArenaArc::try_clone`](crate::ArenaArc::try_clone) if there
+/// are already [`MAX_REFCNT`](crate::MAX_REFCNT) references to the element.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RefCntOverflowError;
+
+impl fmt::Display for RefCntOverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ArenaArc can have at most MAX_REFCNT refcount")
+    }
+}
+
+impl StdError for RefCntOverflowError {}
+
+/// Returned by [`Arena::try_insert`](crate::Arena::try_insert), which gives
+/// the value back.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum InsertError<T> {
+    /// All `len` buckets are full, but more buckets can be reserved.
+    Full { value: T, len: u32 },
+    /// All buckets are full and the `Arena` has reached its limit,
+    /// so a slot has to be freed first.
+    CapacityExhausted { value: T },
+}
+
+impl<T> InsertError<T> {
+    pub fn value(&self) -> &T {
+        match self {
+            InsertError::Full { value, .. } => value,
+            InsertError::CapacityExhausted { value } => value,
+        }
+    }
+
+    pub fn into_value(self) -> T {
+        match self {
+            InsertError::Full { value, .. } => value,
+            InsertError::CapacityExhausted { value } => value,
+        }
+    }
+}
+
+impl<T> fmt::Display for InsertError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InsertError::Full { len, .. } => write!(f, "all {len} buckets are full"),
+            InsertError::CapacityExhausted { .. } => {
+                f.write_str("all buckets are full and the limit is reached")
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug> StdError for InsertError<T> {}
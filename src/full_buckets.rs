@@ -0,0 +1,201 @@
+use super::arcs::{locate, SEGMENTS};
+
+use core::ptr::{self, null_mut};
+use std::sync::atomic::{
+    AtomicPtr, AtomicUsize,
+    Ordering::{Acquire, Relaxed, Release, SeqCst},
+};
+
+/// Hint of which buckets are full, one bit per bucket, so that finding
+/// a bucket with free slots does not need to visit every full bucket.
+///
+/// A set bit only means that the bucket was full at some point,
+/// while a bucket with a free slot always has its bit cleared
+/// once the thread freeing the slot or marking the bucket returns.
+///
+/// The words are stored in segments in the same way as `Arcs`, which are
+/// only freed on drop.
+#[derive(Debug, Default)]
+pub(crate) struct FullBuckets {
+    segments: [AtomicPtr<AtomicUsize>; SEGMENTS],
+}
+
+impl FullBuckets {
+    /// Make sure there is one bit for each of the first `len` buckets.
+    pub(crate) fn grow(&self, len: usize) {
+        let words = len.div_ceil(usize::BITS as usize);
+        if words == 0 {
+            return;
+        }
+
+        let (last, _) = locate(words - 1);
+
+        for (segment, atomic) in self.segments[..=last].iter().enumerate() {
+            if !atomic.load(Acquire).is_null() {
+                continue;
+            }
+
+            let new = Box::into_raw(
+                (0..(1_usize << segment))
+                    .map(|_| AtomicUsize::new(0))
+                    .collect::<Box<[_]>>(),
+            )
+            .cast::<AtomicUsize>();
+
+            if atomic
+                .compare_exchange(null_mut(), new, Release, Acquire)
+                .is_err()
+            {
+                // Another thread has allocated the segment.
+                //
+                // Safety: `new` is just allocated above with this length.
+                drop(unsafe { Self::segment_from_raw(new, segment) });
+            }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` must be the segment `segment` allocated in `grow`.
+    unsafe fn segment_from_raw(ptr: *mut AtomicUsize, segment: usize) -> Box<[AtomicUsize]> {
+        Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, 1 << segment))
+    }
+
+    /// Return the word at `word_index`, or `None` if it is not allocated yet.
+    fn word(&self, word_index: usize) -> Option<&AtomicUsize> {
+        let (segment, offset) = locate(word_index);
+        let ptr = self.segments.get(segment)?.load(Acquire);
+
+        // Safety: segment `segment` has `2^segment` words and it is only
+        // freed on drop.
+        (!ptr.is_null()).then(|| unsafe { &*ptr.add(offset) })
+    }
+
+    /// Return the word holding the bit of `bucket_index` and the mask
+    /// of the bit, or `None` if the bit is not allocated yet.
+    fn locate(&self, bucket_index: u32) -> Option<(&AtomicUsize, usize)> {
+        let bits = usize::BITS;
+
+        let word = self.word((bucket_index / bits) as usize)?;
+        Some((word, 1 << (bucket_index % bits)))
+    }
+
+    /// Mark the bucket as full.
+    ///
+    /// Use `SeqCst` so that the caller can check whether the bucket is
+    /// still full afterwards, see `Bucket::on_full`.
+    pub(crate) fn mark_full(&self, bucket_index: u32) {
+        if let Some((word, mask)) = self.locate(bucket_index) {
+            word.fetch_or(mask, SeqCst);
+        }
+    }
+
+    /// Mark the bucket as having free slots, return true if it was
+    /// marked as full.
+    ///
+    /// The caller must free the slot using a `SeqCst` atomic operation
+    /// before calling this function.
+    pub(crate) fn mark_not_full(&self, bucket_index: u32) -> bool {
+        match self.locate(bucket_index) {
+            // Only write to the word if the bit is set to avoid contention.
+            Some((word, mask)) if word.load(SeqCst) & mask != 0 => {
+                word.fetch_and(!mask, SeqCst) & mask != 0
+            }
+            _ => false,
+        }
+    }
+
+    /// Return indexes of the first `len` buckets that are not marked as
+    /// full, starting at `start` < `len` and wrapping around at `len`.
+    pub(crate) fn not_full(&self, start: usize, len: usize) -> impl Iterator<Item = usize> + '_ {
+        let bits = usize::BITS as usize;
+
+        let words = len.div_ceil(bits);
+        let start_word = start / bits;
+        let start_bit = start % bits;
+
+        // The word of `start` is visited twice, first for the bits at and
+        // after `start`, then for the bits before it.
+        (start_word..words)
+            .chain(0..start_word)
+            .chain((start_bit != 0).then_some(start_word))
+            .enumerate()
+            .flat_map(move |(i, word_index)| {
+                let full = self.word(word_index).map_or(0, |word| word.load(Relaxed));
+                let mut free = !full;
+
+                if word_index == words - 1 && len % bits != 0 {
+                    free &= (1 << (len % bits)) - 1;
+                }
+                if word_index == start_word {
+                    if i == 0 {
+                        free &= usize::MAX << start_bit;
+                    } else {
+                        free &= (1 << start_bit) - 1;
+                    }
+                }
+
+                BitIter(free).map(move |bit| word_index * bits + bit)
+            })
+    }
+}
+
+impl Drop for FullBuckets {
+    fn drop(&mut self) {
+        for (segment, atomic) in self.segments.iter_mut().enumerate() {
+            let ptr = *atomic.get_mut();
+            if !ptr.is_null() {
+                // Safety: `ptr` is allocated in `grow` and there is no other
+                // reference to it, given `&mut self`.
+                drop(unsafe { Self::segment_from_raw(ptr, segment) });
+            }
+        }
+    }
+}
+
+/// Iterator over the set bits in a `usize`, from the lowest one.
+struct BitIter(usize);
+
+impl Iterator for BitIter {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            None
+        } else {
+            let bit = self.0.trailing_zeros() as usize;
+            self.0 &= self.0 - 1;
+            Some(bit)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FullBuckets;
+
+    #[test]
+    fn test() {
+        let bits = usize::BITS as usize;
+        let len = bits * 2 + 3;
+
+        let full = FullBuckets::default();
+        assert_eq!(full.not_full(0, len).count(), len);
+
+        full.grow(len);
+        assert!(full.not_full(5, len).eq((5..len).chain(0..5)));
+        assert!(full.not_full(bits, len).eq((bits..len).chain(0..bits)));
+
+        for i in (0..len).filter(|i| i % 3 != 0) {
+            full.mark_full(i as u32);
+        }
+        assert!(full
+            .not_full(bits + 1, len)
+            .eq((bits + 1..len).chain(0..bits + 1).filter(|i| i % 3 == 0)));
+
+        assert!(full.mark_not_full(bits as u32 + 1));
+        assert!(!full.mark_not_full(bits as u32 + 1));
+        assert!(!full.mark_not_full(0));
+        assert_eq!(full.not_full(bits, len).next(), Some(bits + 1));
+    }
+}
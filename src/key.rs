@@ -0,0 +1,10 @@
+/// Identifies one specific value inserted into an [`Arena`](crate::Arena).
+///
+/// Unlike a bare slot, a `Key` also records the generation of the slot at
+/// the time of insertion, so it stops matching once the value is dropped
+/// and the slot is reused for another value.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Key {
+    pub slot: u32,
+    pub generation: u32,
+}
@@ -2,13 +2,26 @@ mod arcs;
 mod arena;
 mod bitmap;
 mod bucket;
+mod cursor;
+mod error;
+mod full_buckets;
+mod key;
+#[cfg(feature = "rayon")]
+mod par_iter;
+mod shared;
+mod stats;
 mod thread_id;
 
 mod utility;
 use utility::{OptionExt, SliceExt};
 
-pub use arena::Arena;
-pub use bucket::{ArenaArc, MAX_REFCNT};
+pub use arena::{Arena, Iter};
+pub use bucket::{ArenaArc, ArenaBox, ArenaWeak, RefCnt, VacantSlot, MAX_REFCNT};
+pub use error::{Error, InsertError, RefCntOverflowError};
+pub use key::Key;
+#[cfg(feature = "rayon")]
+pub use par_iter::ParIter;
+pub use stats::BucketStats;
 
 /// `triomphe::Arc` does not support weak reference, thus it allocates one `usize` less
 /// than `std::sync::Arc`.
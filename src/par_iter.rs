@@ -0,0 +1,107 @@
+use super::{bucket, Arc, Arena, ArenaArc};
+
+use rayon::iter::{
+    plumbing::UnindexedConsumer, IntoParallelIterator, ParallelExtend, ParallelIterator,
+};
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> Arena<T, BITARRAY_LEN, LEN> {
+    /// Return a parallel iterator over all elements in the `Arena` that
+    /// are not removed, splitting the work per bucket.
+    ///
+    /// It has the same semantics as [`Arena::iter`], except that buckets
+    /// allocated after the iteration starts are not visited.
+    pub fn par_iter(&self) -> ParIter<'_, T, BITARRAY_LEN, LEN> {
+        ParIter { arena: self }
+    }
+}
+
+/// Parallel iterator returned by [`Arena::par_iter`].
+#[derive(Debug)]
+pub struct ParIter<'a, T, const BITARRAY_LEN: usize, const LEN: usize> {
+    arena: &'a Arena<T, BITARRAY_LEN, LEN>,
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> ParallelIterator
+    for ParIter<'_, T, BITARRAY_LEN, LEN>
+{
+    type Item = ArenaArc<T, BITARRAY_LEN, LEN>;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        let slice = self.arena.buckets.as_slice();
+
+        (0..slice.len())
+            .into_par_iter()
+            .flat_map_iter(|bucket_index| {
+                slice
+                    .get(bucket_index)
+                    .map(|bucket| bucket::Iter::new(Arc::clone(&bucket), bucket_index as u32))
+                    .into_iter()
+                    .flatten()
+            })
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<'a, T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> IntoParallelIterator
+    for &'a Arena<T, BITARRAY_LEN, LEN>
+{
+    type Item = ArenaArc<T, BITARRAY_LEN, LEN>;
+    type Iter = ParIter<'a, T, BITARRAY_LEN, LEN>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter()
+    }
+}
+
+impl<T: Send + Sync, const BITARRAY_LEN: usize, const LEN: usize> ParallelExtend<T>
+    for Arena<T, BITARRAY_LEN, LEN>
+{
+    /// Insert every value concurrently.
+    ///
+    /// The `ArenaArc`s returned by [`Arena::insert`] are dropped, the values
+    /// stay in the `Arena` until removed.
+    fn par_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = T>,
+    {
+        let this = &*self;
+
+        par_iter.into_par_iter().for_each(|value| {
+            this.insert(value);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    use rayon::prelude::*;
+
+    const LEN: usize = usize::BITS as usize;
+
+    #[test]
+    fn test_par_iter() {
+        let mut arena: Arena<u32, 1, { LEN }> = Arena::with_capacity(0);
+        assert_eq!(arena.par_iter().count(), 0);
+
+        let n = LEN as u32 * 20;
+        arena.par_extend((0..n).into_par_iter());
+
+        let mut values: Vec<_> = arena.par_iter().map(|arc| *arc).collect();
+        values.sort_unstable();
+        assert!(values.iter().copied().eq(0..n));
+
+        (&arena)
+            .into_par_iter()
+            .filter(|arc| **arc % 2 == 0)
+            .for_each(|arc| assert!(ArenaArc::remove(&arc)));
+
+        let mut values: Vec<_> = arena.par_iter().map(|arc| *arc).collect();
+        values.sort_unstable();
+        assert!(values.iter().copied().eq((0..n).filter(|i| i % 2 != 0)));
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    sync::atomic::{fence, AtomicU32, AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use parking_lot::{Condvar, Mutex};
+
+use super::full_buckets::FullBuckets;
+
+#[cfg(feature = "async")]
+use std::{
+    mem,
+    task::{Context, Poll, Waker},
+};
+
+/// State shared between an `Arena` and all of its buckets, so that it is
+/// still reachable from an `ArenaArc` after the `Arena` is dropped.
+#[derive(Debug, Default)]
+pub(crate) struct Shared {
+    /// Number of threads waiting in `wait_for_slot` plus
+    /// the number of `AsyncWaiter` registered.
+    waiters: AtomicUsize,
+    /// Incremented by every `notify_slot_freed` that has waiters to wake.
+    epoch: AtomicUsize,
+    /// Generation of every entry in a new bucket, which is larger than
+    /// or equal to the generations in all buckets dropped by
+    /// `Arena::shrink_to_fit`, so that a `Key` never matches a value in
+    /// a bucket recreated at the same position.
+    pub(crate) generation_floor: AtomicU32,
+    /// Buckets that are full, so that they can be skipped when reserving
+    /// a slot.
+    pub(crate) full_buckets: FullBuckets,
+    mutex: Mutex<()>,
+    condvar: Condvar,
+    #[cfg(feature = "async")]
+    wakers: Mutex<Wakers>,
+}
+
+/// Wakers of the `AsyncWaiter`s, each tagged with the key of its waiter
+/// so that it can be removed once the waiter is dropped.
+#[cfg(feature = "async")]
+#[derive(Debug, Default)]
+struct Wakers {
+    next_key: usize,
+    wakers: Vec<(usize, Waker)>,
+}
+
+impl Shared {
+    /// Wake up all threads waiting in `wait_for_slot`.
+    ///
+    /// The caller must free the slot, or release the reference being waited
+    /// on, using a `SeqCst` atomic operation or followed by a `SeqCst` fence
+    /// before calling this function, otherwise the wakeup might be lost.
+    pub(crate) fn notify_slot_freed(&self) {
+        // Pairs with the fence in `wait_for_slot`: either the waiter
+        // observes the freed slot, or its increment of `waiters` is
+        // observed here.
+        if self.waiters.load(Ordering::SeqCst) != 0 {
+            self.epoch.fetch_add(1, Ordering::SeqCst);
+
+            // Taking the lock ensures that the waiter either has not yet
+            // checked `epoch` or is already blocked on the condvar.
+            {
+                let _guard = self.mutex.lock();
+                self.condvar.notify_all();
+            }
+
+            #[cfg(feature = "async")]
+            for (_key, waker) in mem::take(&mut self.wakers.lock().wakers) {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Call `f` until it returns `Some(...)`, blocking until
+    /// `notify_slot_freed` is called after every failed attempt.
+    ///
+    /// `f` is called without holding `self.mutex`, since it may free a slot
+    /// and call `notify_slot_freed` itself.
+    ///
+    /// Return `None` if `deadline` is reached before `f` succeeds.
+    pub(crate) fn wait_for_slot<R>(
+        &self,
+        deadline: Option<Instant>,
+        mut f: impl FnMut() -> Option<R>,
+    ) -> Option<R> {
+        self.waiters.fetch_add(1, Ordering::SeqCst);
+        fence(Ordering::SeqCst);
+
+        let res = loop {
+            // Load `epoch` before calling `f`, so that a slot freed after
+            // `f` fails changes it and the wait below is skipped.
+            let epoch = self.epoch.load(Ordering::SeqCst);
+
+            if let Some(res) = f() {
+                break Some(res);
+            }
+
+            let mut guard = self.mutex.lock();
+            if self.epoch.load(Ordering::SeqCst) != epoch {
+                continue;
+            }
+
+            match deadline {
+                Some(deadline) => {
+                    if self.condvar.wait_until(&mut guard, deadline).timed_out() {
+                        drop(guard);
+
+                        // Give it a last try in case the slot is freed
+                        // right before the timeout.
+                        break f();
+                    }
+                }
+                None => self.condvar.wait(&mut guard),
+            }
+        };
+
+        self.waiters.fetch_sub(1, Ordering::Relaxed);
+
+        res
+    }
+}
+
+/// Asynchronous version of `Shared::wait_for_slot`.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub(crate) struct AsyncWaiter<'a> {
+    shared: &'a Shared,
+    /// Key of the waker registered, set once it is counted in `waiters`.
+    key: Option<usize>,
+}
+
+#[cfg(feature = "async")]
+impl<'a> AsyncWaiter<'a> {
+    pub(crate) fn new(shared: &'a Shared) -> Self {
+        Self { shared, key: None }
+    }
+
+    /// Call `f`, if it returns `None` then register `cx.waker()` to be woken
+    /// up by `Shared::notify_slot_freed`.
+    pub(crate) fn poll_slot<R>(
+        &mut self,
+        cx: &mut Context<'_>,
+        mut f: impl FnMut() -> Option<R>,
+    ) -> Poll<R> {
+        if let Some(res) = f() {
+            return Poll::Ready(res);
+        }
+
+        {
+            let mut wakers = self.shared.wakers.lock();
+
+            let key = match self.key {
+                Some(key) => key,
+                None => {
+                    self.shared.waiters.fetch_add(1, Ordering::SeqCst);
+
+                    let key = wakers.next_key;
+                    wakers.next_key = key.wrapping_add(1);
+                    *self.key.insert(key)
+                }
+            };
+
+            // The waker might already be taken by `notify_slot_freed`.
+            match wakers.wakers.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, waker)) if waker.will_wake(cx.waker()) => (),
+                Some((_, waker)) => *waker = cx.waker().clone(),
+                None => wakers.wakers.push((key, cx.waker().clone())),
+            }
+        }
+
+        // The waker must be registered before retrying, so that a slot freed
+        // after the retry wakes it up.
+        fence(Ordering::SeqCst);
+
+        match f() {
+            Some(res) => Poll::Ready(res),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for AsyncWaiter<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            // Remove the waker, so that cancelled waiters do not pile up.
+            self.shared.wakers.lock().wakers.retain(|(k, _)| *k != key);
+
+            self.shared.waiters.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Shared;
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread::{sleep, spawn};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_timeout() {
+        let shared = Shared::default();
+
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(50);
+
+        assert_eq!(shared.wait_for_slot(Some(deadline), || None::<()>), None);
+        assert!(Instant::now() >= deadline);
+    }
+
+    #[test]
+    fn test_notify() {
+        let shared = Arc::new(Shared::default());
+        let freed = Arc::new(AtomicBool::new(false));
+
+        let handle = {
+            let shared = shared.clone();
+            let freed = freed.clone();
+
+            spawn(move || {
+                sleep(Duration::from_millis(10));
+                freed.store(true, Ordering::SeqCst);
+                shared.notify_slot_freed();
+            })
+        };
+
+        let res = shared.wait_for_slot(None, || freed.load(Ordering::Relaxed).then_some(1));
+        assert_eq!(res, Some(1));
+
+        handle.join().unwrap();
+    }
+}
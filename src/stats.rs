@@ -0,0 +1,16 @@
+/// Snapshot of the occupancy of one bucket, returned by
+/// [`Arena::occupancy`](crate::Arena::occupancy).
+///
+/// The counters are loaded one by one while other threads may be
+/// modifying the bucket, so they are not necessarily consistent
+/// with each other.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct BucketStats {
+    /// Number of elements that are not removed.
+    pub live: u32,
+    /// Number of elements that are removed but still referenced.
+    pub zombies: u32,
+    /// Number of slots allocated, including slots reserved by a
+    /// [`VacantSlot`](crate::VacantSlot) or an [`ArenaBox`](crate::ArenaBox).
+    pub allocated: u32,
+}